@@ -14,7 +14,6 @@ use collections::HashMap;
 
 use highlight::{backend, core};
 use highlight::backend::Backend;
-use highlight::core::{Start,End,Text};
 
 struct Args {
     show_help: bool,
@@ -22,14 +21,20 @@ struct Args {
     backend: backend::BackendType,
     backend_vars: HashMap<~str, ~str>,
 
+    options: core::Options,
+
     header: bool,
+    check: bool,
+    lines: Option<(uint, uint)>,
     output_filename: Option<~str>,
     filename: Option<~str>,
 }
 
 fn parse_args(argv: &[~str], opts: &[getopts::OptGroup]) -> Result<Args, ~str> {
     fn select_backend(matches: &getopts::Matches) -> Result<backend::BackendType, ~str> {
-        if matches.opt_present("html") {
+        if matches.opt_present("ansi") {
+            Ok(backend::Ansi)
+        } else if matches.opt_present("html") {
             Ok(backend::Html)
         } else if matches.opt_present("json") {
             Ok(backend::Json)
@@ -40,6 +45,20 @@ fn parse_args(argv: &[~str], opts: &[getopts::OptGroup]) -> Result<Args, ~str> {
         }
     }
 
+    // Reads the colour count of a flag, given by the option `count`: 0 if
+    // neither is present, `default` if the count isn't. The count is a
+    // separate option, as an optional value would take the filename.
+    fn count_opt(matches: &getopts::Matches, name: &str, count: &str, default: uint) -> Result<uint, ~str> {
+        match matches.opt_str(count) {
+            Some(value) => match from_str::<uint>(value.as_slice()) {
+                Some(n) if n > 0 => Ok(n),
+                _ => Err(format!("Bad `{}` colour count: {}", count, value)),
+            },
+            None if matches.opt_present(name) => Ok(default),
+            None => Ok(0),
+        }
+    }
+
     let argv: Vec<StrBuf> = argv.iter().map(|s| s.to_strbuf()).collect();
     let matches = match getopts::getopts(argv.as_slice(), opts) {
         Ok(m) => m,
@@ -59,7 +78,11 @@ fn parse_args(argv: &[~str], opts: &[getopts::OptGroup]) -> Result<Args, ~str> {
         },
         backend_vars: HashMap::new(),
 
+        options: core::Options::new(),
+
         header: matches.opt_present("header"),
+        check: matches.opt_present("check"),
+        lines: None,
         output_filename: matches.opt_str("output").map(|s| s.into_owned()),
         filename: match matches.free.len() {
             0 => None,
@@ -67,6 +90,46 @@ fn parse_args(argv: &[~str], opts: &[getopts::OptGroup]) -> Result<Args, ~str> {
         }
     };
 
+    args.options.semantic = matches.opt_present("semantic");
+
+    args.options.rainbow = try!(count_opt(&matches, "rainbow", "rainbow-colors", 6));
+    args.options.lifetimes = try!(count_opt(&matches, "lifetimes", "lifetime-colors", 6));
+    args.options.variables = try!(count_opt(&matches, "variables", "variable-colors", 8));
+
+    match matches.opt_str("lines") {
+        Some(range) => {
+            let bounds: Vec<Option<uint>> = range.as_slice().split(':').map(|n| from_str(n)).collect();
+            args.lines = match bounds.as_slice() {
+                [Some(first), Some(last)] if first >= 1 && first <= last => Some((first, last)),
+                _ => {
+                    return Err(format!("Bad line range: {}", range));
+                }
+            };
+        }
+        None => {}
+    }
+
+    match matches.opt_str("markers") {
+        Some(markers) => {
+            args.options.markers = markers.as_slice().split(',')
+                .map(|m| m.trim()).filter(|m| m.len() > 0)
+                .map(|m| m.to_owned()).collect();
+        }
+        None => {}
+    }
+
+    match matches.opt_str("edition") {
+        Some(edition) => {
+            args.options.edition = match from_str(edition.as_slice()) {
+                Some(edition) => edition,
+                None => {
+                    return Err(format!("Bad edition: {}", edition));
+                }
+            };
+        }
+        None => {}
+    }
+
     for var in matches.opt_strs("var").iter() {
         let value = var.as_slice();
         let parts: Vec<&str> = value.splitn('=', 1).collect();
@@ -77,6 +140,21 @@ fn parse_args(argv: &[~str], opts: &[getopts::OptGroup]) -> Result<Args, ~str> {
         args.backend_vars.insert(name.to_owned(), value.to_owned());
     }
 
+    match matches.opt_str("names") {
+        Some(filename) => {
+            let path = Path::new(filename.as_slice());
+            let text = match io::File::open(&path).read_to_str() {
+                Ok(text) => text,
+                Err(e) => {
+                    return Err(format!("Read error: {} ({})", e, filename));
+                }
+            };
+            try!(args.options.names.load(text.as_slice()));
+        }
+        None => {}
+    }
+    try!(args.options.names.configure(&args.backend_vars));
+
     Ok(args)
 }
 
@@ -97,9 +175,22 @@ fn main() {
         getopts::optflag("h", "help", "Show this help and exit."),
         getopts::optopt("o", "output", "Output filename.", "FILENAME"),
         getopts::optflag("", "header", "Output head to put before highlighted code."),
+        getopts::optflag("", "check", "Check that the highlighted text is the source, and output nothing."),
+        getopts::optflag("", "ansi", "Output code for terminals (24-bit ANSI colours)."),
         getopts::optflag("", "html", "Output HTML code."),
         getopts::optflag("", "json", "Output JSON code."),
         getopts::optflag("", "latex", "Output LaTeX code."),
+        getopts::optopt("", "lines", "Only output these lines, highlighted within the whole file.", "FIRST:LAST"),
+        getopts::optopt("", "edition", "Rust edition (2015, 2018, 2021 or 2024).", "YEAR"),
+        getopts::optflag("", "semantic", "Classify identifiers by their syntactic position."),
+        getopts::optflag("", "rainbow", "Colour delimiters by nesting depth."),
+        getopts::optopt("", "rainbow-colors", "Number of colours of --rainbow (6 by default).", "N"),
+        getopts::optflag("", "lifetimes", "Colour each lifetime on its own."),
+        getopts::optopt("", "lifetime-colors", "Number of colours of --lifetimes (6 by default).", "N"),
+        getopts::optflag("", "variables", "Colour each local variable on its own."),
+        getopts::optopt("", "variable-colors", "Number of colours of --variables (8 by default).", "N"),
+        getopts::optopt("", "markers", "Words to highlight in comments (TODO,FIXME,XXX,SAFETY:,HACK by default).", "WORDS"),
+        getopts::optopt("", "names", "Read classes of names (`class = Name, Name` lines).", "FILENAME"),
         getopts::optmulti("v", "var", "Set backend-specific variables.", "KEY=VAL"),
     ];
 
@@ -149,29 +240,43 @@ fn main() {
         }
     };
 
-    let src = match input.read_to_end() {
+    let bytes = match input.read_to_end() {
         Ok(s) => s,
         Err(f) => {
             fail!("Read error: {} ({})", f, args.filename.unwrap_or("stdin".into_owned()));
         }
     };
-    let src = str::from_utf8(src.as_slice()).unwrap();
+    // Invalid UTF-8 sequences are replaced rather than aborting the run, except
+    // with `--check`, which compares the parts with the file itself.
+    let src = str::from_utf8_lossy(bytes.as_slice()).into_owned();
 
-    let parts = core::highlight(src);
+    if args.check {
+        let name = args.filename.clone().unwrap_or("stdin".into_owned());
+        let parts = core::Highlighter::with_options(src.as_slice(), args.options.clone());
+        match core::check(bytes.as_slice(), parts) {
+            Some(offset) => {
+                let line = bytes.as_slice().slice_to(offset).iter().filter(|&&b| b == '\n' as u8).count() + 1;
+                let mut stderr = io::stderr();
+                writeln!(&mut stderr, "{}: highlighted text differs from the source at byte {} (line {})",
+                         name, offset, line);
+                os::set_exit_status(1);
+            }
+            None => {}
+        }
+        return;
+    }
 
     backend.code_start(output);
-    for part in parts.iter() {
-        match *part {
-            Start(ref ty) => {
-                backend.start(output, ty.as_slice());
+    let highlighter = core::Highlighter::with_options(src.as_slice(), args.options.clone());
+    match args.lines {
+        Some((first, last)) => {
+            for part in core::range::LineRange::new(highlighter, first, last) {
+                backend.part(output, &part);
             }
-            End(ref ty) => {
-                backend.end(output, ty.as_slice());
-            }
-            Text(ref ty, ref text) => {
-                backend.start(output, ty.as_slice());
-                backend.text(output, text.as_slice());
-                backend.end(output, ty.as_slice());
+        }
+        None => {
+            for part in highlighter {
+                backend.part(output, &part);
             }
         }
     }
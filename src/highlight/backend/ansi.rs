@@ -0,0 +1,83 @@
+use std::io::IoResult;
+use std::num;
+
+use collections::HashMap;
+
+use backend::Backend;
+use colors;
+use core::Span;
+
+/// Outputs the code with ANSI escape sequences, for terminals supporting 24-bit
+/// colours.
+pub struct AnsiBackend {
+    // colour of each open region, inherited from the enclosing one when the
+    // region has none
+    colors: Vec<Option<~str>>,
+}
+
+impl AnsiBackend {
+    pub fn new() -> AnsiBackend {
+        AnsiBackend {
+            colors: Vec::new(),
+        }
+    }
+
+    fn set_color(&self, w: &mut Writer) -> IoResult<()> {
+        match self.colors.last() {
+            Some(&Some(ref color)) => {
+                let component = |i: uint| {
+                    num::from_str_radix::<u8>(color.slice(i, i + 2), 16).unwrap_or(0)
+                };
+                write!(w, "\x1b[38;2;{};{};{}m", component(0), component(2), component(4))
+            }
+            _ => w.write_str("\x1b[0m"),
+        }
+    }
+}
+
+impl Backend for AnsiBackend {
+    fn configure(&mut self, _vars: &HashMap<~str, ~str>) -> Result<(), ~str> {
+        Ok(())
+    }
+
+    fn header(&mut self, _w: &mut Writer) -> IoResult<()> {
+        Ok(())
+    }
+
+    fn code_start(&mut self, _w: &mut Writer) -> IoResult<()> {
+        Ok(())
+    }
+
+    fn code_end(&mut self, w: &mut Writer) -> IoResult<()> {
+        try!(w.write_str("\x1b[0m"));
+
+        Ok(())
+    }
+
+    fn start(&mut self, w: &mut Writer, ty: &str, _span: &Span) -> IoResult<()> {
+        let color = match colors::get_color(ty) {
+            Some(color) => Some(color),
+            None => match self.colors.last() {
+                Some(color) => color.clone(),
+                None => None,
+            },
+        };
+        self.colors.push(color);
+        try!(self.set_color(w));
+
+        Ok(())
+    }
+
+    fn end(&mut self, w: &mut Writer, _ty: &str, _span: &Span) -> IoResult<()> {
+        self.colors.pop();
+        try!(self.set_color(w));
+
+        Ok(())
+    }
+
+    fn text(&mut self, w: &mut Writer, text: &str, _span: &Span) -> IoResult<()> {
+        try!(w.write_str(text));
+
+        Ok(())
+    }
+}
@@ -5,16 +5,20 @@ use std::str;
 use collections::HashMap;
 
 use colors;
+use core::Span;
 use backend::Backend;
 
 pub struct HtmlBackend {
     pub use_classes: bool,
+    // set inside a `url` region, whose text is output as a link
+    in_url: bool,
 }
 
 impl HtmlBackend {
     pub fn new() -> HtmlBackend {
         HtmlBackend {
             use_classes: false,
+            in_url: false,
         }
     }
 }
@@ -53,6 +57,9 @@ impl Backend for HtmlBackend {
                 let color = colors::get_color(ty.as_slice()).unwrap();
                 try!(writeln!(w, "    pre.rust .{} \\{ color: \\#{}; \\}", ty, color));
             }
+            for (ty, color) in colors::get_backgrounds().iter() {
+                try!(writeln!(w, "    pre.rust .{} \\{ background-color: \\#{}; \\}", ty, color));
+            }
             try!(w.write_line("</style>"));
         }
 
@@ -71,8 +78,12 @@ impl Backend for HtmlBackend {
         Ok(())
     }
 
-    fn start(&mut self, w: &mut Writer, ty: &str) -> IoResult<()> {
-        if ty != "" && ty != "normal" {
+    fn start(&mut self, w: &mut Writer, ty: &str, _span: &Span) -> IoResult<()> {
+        if ty == "url" {
+            self.in_url = true;
+        }
+
+        if ty != "" && ty != "whitespace" {
             try!(w.write_str("<span"));
 
             if self.use_classes {
@@ -85,31 +96,40 @@ impl Backend for HtmlBackend {
                     }
                     None => {}
                 }
+                let background = colors::get_background(ty);
+                match background {
+                    Some(c) => {
+                        try!(write!(w, " style=\"background-color: \\#{};\"", c));
+                    }
+                    None => {}
+                }
             }
 
             try!(w.write_str(">"));
         }
 
-        if ty == "attribute" {
-            try!(w.write_str("#"));
-        }
-
         Ok(())
     }
 
-    fn end(&mut self, w: &mut Writer, ty: &str) -> IoResult<()> {
-        if ty == "attribute" {
-            try!(w.write_str("]"));
+    fn end(&mut self, w: &mut Writer, ty: &str, _span: &Span) -> IoResult<()> {
+        if ty == "url" {
+            self.in_url = false;
         }
 
-        if ty != "" && ty != "normal" {
+        if ty != "" && ty != "whitespace" {
             try!(w.write_str("</span>"));
         }
 
         Ok(())
     }
 
-    fn text(&mut self, w: &mut Writer, text: &str) -> IoResult<()> {
+    fn text(&mut self, w: &mut Writer, text: &str, _span: &Span) -> IoResult<()> {
+        if self.in_url {
+            let text = escape_html(text);
+            try!(write!(w, "<a href=\"{}\">{}</a>", text, text));
+            return Ok(());
+        }
+
         try!(w.write_str(escape_html(text)));
 
         Ok(())
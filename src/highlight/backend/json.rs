@@ -5,27 +5,55 @@ use serialize::{json, Encodable, Encoder};
 
 use backend::Backend;
 use colors;
+use core::{Part, Start, End, Text, Span};
+use core::tree::TreeBuilder;
 
 pub struct JsonBackend {
     pub pretty: bool,
+    /// Output a tree of nodes instead of a flat list of entries.
+    pub tree: bool,
 
-    entries: Vec<Entry>,
+    first: bool,
+    builder: Option<TreeBuilder>,
 }
 
 #[deriving(Encodable)]
 struct Entry {
     ty: ~str,
     value: ~str,
+    span: Span,
 }
 
 impl JsonBackend {
     pub fn new() -> JsonBackend {
         JsonBackend {
             pretty: false,
+            tree: false,
 
-            entries: Vec::new(),
+            first: true,
+            builder: None,
         }
     }
+
+    // Entries are written as soon as they are received, so that the output
+    // never has to be buffered.
+    fn write_entry(&mut self, w: &mut Writer, entry: Entry) -> IoResult<()> {
+        if self.first {
+            self.first = false;
+        } else {
+            try!(w.write_str(","));
+        }
+
+        // Pretty output puts one entry per line.
+        if self.pretty {
+            try!(w.write_str("\n  "));
+        }
+
+        let mut encoder = json::Encoder::new(w);
+        try!(entry.encode(&mut encoder));
+
+        Ok(())
+    }
 }
 
 impl Backend for JsonBackend {
@@ -42,6 +70,18 @@ impl Backend for JsonBackend {
             None => {}
         }
 
+        match vars.find_equiv::<&str>(&"tree") {
+            Some(ref value) => {
+                self.tree = match from_str(value.as_slice()) {
+                    Some(b) => b,
+                    None => {
+                        return Err(format!("Bad value for tree: {}", value));
+                    }
+                }
+            }
+            None => {}
+        }
+
         Ok(())
     }
 
@@ -59,46 +99,84 @@ impl Backend for JsonBackend {
         Ok(())
     }
 
-    fn code_start(&mut self, _w: &mut Writer) -> IoResult<()> {
+    fn code_start(&mut self, w: &mut Writer) -> IoResult<()> {
+        // The tree can only be written once all the parts are known.
+        if self.tree {
+            self.builder = Some(TreeBuilder::new());
+            return Ok(());
+        }
+
+        self.first = true;
+        try!(w.write_str("["));
+
         Ok(())
     }
 
     fn code_end(&mut self, w: &mut Writer) -> IoResult<()> {
-        if self.pretty {
-            let mut encoder = json::PrettyEncoder::new(w);
-            try!(self.entries.encode(&mut encoder));
-        } else {
-            let mut encoder = json::Encoder::new(w);
-            try!(self.entries.encode(&mut encoder));
+        match self.builder.take() {
+            Some(builder) => {
+                let nodes = builder.finish();
+                if self.pretty {
+                    let mut encoder = json::PrettyEncoder::new(w);
+                    try!(nodes.encode(&mut encoder));
+                } else {
+                    let mut encoder = json::Encoder::new(w);
+                    try!(nodes.encode(&mut encoder));
+                }
+                return Ok(());
+            }
+            None => {}
         }
 
+        if self.pretty && !self.first {
+            try!(w.write_str("\n"));
+        }
+        try!(w.write_str("]"));
+
         Ok(())
     }
 
-    fn start(&mut self, _w: &mut Writer, ty: &str) -> IoResult<()> {
-        self.entries.push(Entry {
+    fn part(&mut self, w: &mut Writer, part: &Part) -> IoResult<()> {
+        match self.builder {
+            Some(ref mut builder) => {
+                builder.push(part);
+                return Ok(());
+            }
+            None => {}
+        }
+
+        match *part {
+            Start(ref ty, ref span) => self.start(w, ty.as_slice(), span),
+            End(ref ty, ref span) => self.end(w, ty.as_slice(), span),
+            Text(ref ty, ref text, ref span) => {
+                try!(self.start(w, ty.as_slice(), span));
+                try!(self.text(w, text.as_slice(), span));
+                self.end(w, ty.as_slice(), span)
+            }
+        }
+    }
+
+    fn start(&mut self, w: &mut Writer, ty: &str, span: &Span) -> IoResult<()> {
+        self.write_entry(w, Entry {
             ty: "start".into_owned(),
             value: ty.to_owned(),
-        });
-
-        Ok(())
+            span: span.clone(),
+        })
     }
 
-    fn end(&mut self, _w: &mut Writer, ty: &str) -> IoResult<()> {
-        self.entries.push(Entry {
+    fn end(&mut self, w: &mut Writer, ty: &str, span: &Span) -> IoResult<()> {
+        self.write_entry(w, Entry {
             ty: "end".into_owned(),
             value: ty.to_owned(),
-        });
-
-        Ok(())
+            span: span.clone(),
+        })
     }
 
-    fn text(&mut self, _w: &mut Writer, text: &str) -> IoResult<()> {
-        self.entries.push(Entry {
+    fn text(&mut self, w: &mut Writer, text: &str, span: &Span) -> IoResult<()> {
+        self.write_entry(w, Entry {
             ty: "text".into_owned(),
             value: text.to_owned(),
-        });
-
-        Ok(())
+            span: span.clone(),
+        })
     }
 }
@@ -4,15 +4,19 @@ use collections::HashMap;
 
 use backend::Backend;
 use colors;
+use core::Span;
 
 pub struct LatexBackend {
     contexts: Vec<~str>,
+    // classes of the open regions with a background colour
+    backgrounds: Vec<~str>,
 }
 
 impl LatexBackend {
     pub fn new() -> LatexBackend {
         LatexBackend {
             contexts: Vec::new(),
+            backgrounds: Vec::new(),
         }
     }
 }
@@ -25,8 +29,18 @@ static HEADER: &'static str = "\
 \\DefineVerbatimEnvironment{Highlighting}{Verbatim}{commandchars=\\\\\\{\\}}
 % Add ',fontsize=\\small' for more characters per line
 \\newenvironment{Shaded}{}{}
+\\newcommand{\\Background}[2]{{\\setlength{\\fboxsep}{0pt}\\colorbox{#1}{#2}}}
 ";
 
+// Comments may span several lines, which a single `\textcolor` cannot do
+// inside the verbatim environment, so they are coloured line by line.
+fn is_comment(ty: &str) -> bool {
+    match ty {
+        "comment" | "block-comment" | "doccomment" | "inner-doccomment" => true,
+        _ => false,
+    }
+}
+
 impl Backend for LatexBackend {
     fn configure(&mut self, _vars: &HashMap<~str, ~str>) -> Result<(), ~str> {
         Ok(())
@@ -38,6 +52,9 @@ impl Backend for LatexBackend {
         for (ty, color) in colors::get_colors().iter() {
             try!(writeln!(w, "\\\\definecolor\\{{}\\}\\{HTML\\}\\{{}\\}", ty, color));
         }
+        for (ty, color) in colors::get_backgrounds().iter() {
+            try!(writeln!(w, "\\\\definecolor\\{{}\\}\\{HTML\\}\\{{}\\}", ty, color));
+        }
 
         Ok(())
     }
@@ -56,68 +73,64 @@ impl Backend for LatexBackend {
         Ok(())
     }
 
-    fn start(&mut self, w: &mut Writer, ty: &str) -> IoResult<()> {
-        if ty != "comment" {
+    fn start(&mut self, w: &mut Writer, ty: &str, _span: &Span) -> IoResult<()> {
+        if !is_comment(ty) {
             if colors::get_types().contains(&ty.to_owned()) {
                 try!(write!(w, "\\\\textcolor\\{{}\\}\\{", ty));
             }
+        }
 
-            if ty == "attribute" {
-                try!(w.write_str("#"));
-            }
+        if colors::get_background(ty).is_some() {
+            self.backgrounds.push(ty.to_owned());
         }
 
-        self.contexts.push(ty.to_owned());
+        // Text without a class of its own is written like the one of the
+        // enclosing region, which matters for comments.
+        let context = match self.contexts.last() {
+            Some(context) if ty == "" => context.clone(),
+            _ => ty.to_owned(),
+        };
+        self.contexts.push(context);
 
         Ok(())
     }
 
-    fn end(&mut self, w: &mut Writer, ty: &str) -> IoResult<()> {
-        if ty != "comment" {
-            if ty == "attribute" {
-                try!(w.write_str("]"));
-            }
-
+    fn end(&mut self, w: &mut Writer, ty: &str, _span: &Span) -> IoResult<()> {
+        if !is_comment(ty) {
             if colors::get_types().contains(&ty.to_owned()) {
                 try!(w.write_str("}"));
             }
         }
 
+        if colors::get_background(ty).is_some() {
+            self.backgrounds.pop();
+        }
         self.contexts.pop();
 
         Ok(())
     }
 
-    fn text(&mut self, w: &mut Writer, text: &str) -> IoResult<()> {
+    fn text(&mut self, w: &mut Writer, text: &str, _span: &Span) -> IoResult<()> {
         fn escape_latex(text: &str) -> ~str {
             let mut result = StrBuf::new();
 
-            let mut escape = false;
+            // A backslash is printed on its own: wrapping the character after
+            // it in a group breaks on line continuations inside strings.
             for c in text.chars() {
-                if escape {
-                    result.push_str("\\textbackslash{");
-                }
-
-                if c == '{' || c == '}' {
-                    result.push_char('\\');
-                }
-
-                if c == '\\' {
-                    escape = true;
-                } else {
-                    result.push_char(c);
-                }
-
-                if escape && c != '\\' {
-                    result.push_char('}');
-                    escape = false;
+                match c {
+                    '{' | '}' => {
+                        result.push_char('\\');
+                        result.push_char(c);
+                    }
+                    '\\' => result.push_str("\\textbackslash{}"),
+                    _ => result.push_char(c),
                 }
             }
 
             result.into_owned()
         }
 
-        fn escape_comment(text: &str, has_color: bool) -> ~str {
+        fn escape_comment(text: &str, ty: &str, has_color: bool) -> ~str {
             let mut result = StrBuf::new();
 
             let mut first = true;
@@ -127,7 +140,7 @@ impl Backend for LatexBackend {
                 }
 
                 if line.len() > 0 && has_color {
-                    result.push_str("\\textcolor{comment}{");
+                    result.push_str(format!("\\\\textcolor\\{{}\\}\\{", ty));
                 }
 
                 result.push_str(line);
@@ -152,15 +165,35 @@ impl Backend for LatexBackend {
             result.into_owned()
         }
 
+        // Like comments, backgrounds are drawn line by line.
+        fn with_background(text: &str, ty: &str) -> ~str {
+            let mut result = StrBuf::new();
+
+            for (i, line) in text.split('\n').enumerate() {
+                if i > 0 {
+                    result.push_char('\n');
+                }
+                if line.len() > 0 {
+                    result.push_str(format!("\\\\Background\\{{}\\}\\{{}\\}", ty, line));
+                }
+            }
+
+            result.into_owned()
+        }
+
         let context = self.contexts.last().unwrap();
         let has_color = colors::get_types().contains(context);
         let context = context.as_slice();
 
-        let text = if context == "comment" {
-            escape_comment(text, has_color)
+        let text = if is_comment(context) {
+            escape_comment(text, context, has_color)
         } else {
             escape_latex(text)
         };
+        let text = match self.backgrounds.last() {
+            Some(ty) => with_background(text, ty.as_slice()),
+            None => text,
+        };
         try!(w.write_str(text));
 
         Ok(())
@@ -2,11 +2,15 @@ use std::io::IoResult;
 
 use collections::HashMap;
 
+use core::{Part, Start, End, Text, Span};
+
+pub mod ansi;
 pub mod html;
 pub mod json;
 pub mod latex;
 
 pub enum BackendType {
+    Ansi,
     Html,
     Json,
     Latex,
@@ -20,13 +24,27 @@ pub trait Backend {
     fn code_start(&mut self, w: &mut Writer) -> IoResult<()>;
     fn code_end(&mut self, w: &mut Writer) -> IoResult<()>;
 
-    fn start(&mut self, w: &mut Writer, ty: &str) -> IoResult<()>;
-    fn end(&mut self, w: &mut Writer, ty: &str) -> IoResult<()>;
-    fn text(&mut self, w: &mut Writer, text: &str) -> IoResult<()>;
+    fn start(&mut self, w: &mut Writer, ty: &str, span: &Span) -> IoResult<()>;
+    fn end(&mut self, w: &mut Writer, ty: &str, span: &Span) -> IoResult<()>;
+    fn text(&mut self, w: &mut Writer, text: &str, span: &Span) -> IoResult<()>;
+
+    /// Outputs a part. A text is output inside a region of its class.
+    fn part(&mut self, w: &mut Writer, part: &Part) -> IoResult<()> {
+        match *part {
+            Start(ref ty, ref span) => self.start(w, ty.as_slice(), span),
+            End(ref ty, ref span) => self.end(w, ty.as_slice(), span),
+            Text(ref ty, ref text, ref span) => {
+                try!(self.start(w, ty.as_slice(), span));
+                try!(self.text(w, text.as_slice(), span));
+                self.end(w, ty.as_slice(), span)
+            }
+        }
+    }
 }
 
 pub fn new_backend(ty: BackendType) -> Box<Backend> {
     match ty {
+        Ansi => box ansi::AnsiBackend::new() as Box<Backend>,
         Html => box html::HtmlBackend::new() as Box<Backend>,
         Json => box json::JsonBackend::new() as Box<Backend>,
         Latex => box latex::LatexBackend::new() as Box<Backend>,
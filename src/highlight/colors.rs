@@ -7,17 +7,93 @@ pub fn get_colors() -> HashMap<&'static str, &'static str> {
     map.insert("kw-2", "4271AE");
     map.insert("prelude-ty", "4271AE");
     map.insert("number", "718C00");
+    map.insert("number-prefix", "8E908C");
+    map.insert("number-exponent", "3E999F");
+    map.insert("number-suffix", "F5871F");
     map.insert("string", "718C00");
+    map.insert("format-placeholder", "3E999F");
+    map.insert("format-spec", "F5871F");
+    map.insert("format-escape", "3E999F");
+    map.insert("escape", "3E999F");
+    map.insert("escape-invalid", "FF0000");
     map.insert("self", "C82829");
     map.insert("boolval", "C82829");
     map.insert("prelude-val", "C82829");
     map.insert("attribute", "C82829");
+    map.insert("inner-attribute", "C82829");
     // map.insert("ident", "C82829");
     map.insert("comment", "8E908C");
+    map.insert("block-comment", "8E908C");
     map.insert("doccomment", "4D4D4C");
+    map.insert("inner-doccomment", "4D4D4C");
     map.insert("macro", "3E999F");
     map.insert("macro-nonterminal", "3E999F");
+    map.insert("macro-fragment", "4271AE");
+    map.insert("macro-repetition", "F5871F");
+    map.insert("macro-separator", "F5871F");
     map.insert("lifetime", "B76514");
+    map.insert("lifetime-0", "B76514");
+    map.insert("lifetime-1", "3E999F");
+    map.insert("lifetime-2", "8959A8");
+    map.insert("lifetime-3", "718C00");
+    map.insert("lifetime-4", "C82829");
+    map.insert("lifetime-5", "4271AE");
+    map.insert("lifetime-static", "F5871F");
+    map.insert("lifetime-anon", "8E908C");
+    map.insert("type", "EAB700");
+    map.insert("trait", "EAB700");
+    map.insert("generic", "EAB700");
+    map.insert("function", "4271AE");
+    map.insert("method", "4271AE");
+    map.insert("field", "C82829");
+    map.insert("variant", "F5871F");
+    map.insert("constant", "F5871F");
+    map.insert("module", "8959A8");
+    map.insert("label", "B76514");
+    map.insert("raw-ident", "4D4D4C");
+    map.insert("byte", "718C00");
+    map.insert("byte-string", "718C00");
+    map.insert("c-string", "718C00");
+    map.insert("range-op", "3E999F");
+    map.insert("try-op", "F5871F");
+    map.insert("error", "FF0000");
+    map.insert("doc-heading", "8959A8");
+    map.insert("doc-emphasis", "3E999F");
+    map.insert("doc-strong", "C82829");
+    map.insert("doc-code", "718C00");
+    map.insert("doc-link", "4271AE");
+    map.insert("doc-fence", "8E908C");
+    map.insert("shebang", "8E908C");
+    map.insert("frontmatter-fence", "8E908C");
+    map.insert("toml-table", "8959A8");
+    map.insert("toml-key", "4271AE");
+    map.insert("marker", "F5871F");
+    map.insert("url", "4271AE");
+    map.insert("var-0", "C82829");
+    map.insert("var-1", "F5871F");
+    map.insert("var-2", "718C00");
+    map.insert("var-3", "3E999F");
+    map.insert("var-4", "4271AE");
+    map.insert("var-5", "8959A8");
+    map.insert("var-6", "B76514");
+    map.insert("var-7", "EAB700");
+    map.insert("delim-0", "C82829");
+    map.insert("delim-1", "F5871F");
+    map.insert("delim-2", "EAB700");
+    map.insert("delim-3", "718C00");
+    map.insert("delim-4", "3E999F");
+    map.insert("delim-5", "8959A8");
+    map.insert("delim-unmatched", "FF0000");
+
+    map
+}
+
+// Classes of regions which are highlighted with a background colour.
+pub fn get_backgrounds() -> HashMap<&'static str, &'static str> {
+    let mut map = HashMap::new();
+
+    map.insert("unsafe-block", "FBE9E7");
+    map.insert("doc-code-block", "F7F7F7");
 
     map
 }
@@ -29,3 +105,7 @@ pub fn get_types() -> Vec<~str> {
 pub fn get_color(ty: &str) -> Option<~str> {
     get_colors().find_equiv::<&str>(&ty).map(|k| k.to_owned())
 }
+
+pub fn get_background(ty: &str) -> Option<~str> {
+    get_backgrounds().find_equiv::<&str>(&ty).map(|k| k.to_owned())
+}
@@ -0,0 +1,96 @@
+// Splitting of comments, so that marker words (`TODO`, `FIXME`...) are in
+// `marker` regions and URLs in `url` regions.
+
+use super::lexer::is_ident_continue;
+use super::literal::{Segment, Open, Close, Piece};
+
+/// The marker words looked for by default.
+pub static MARKERS: &'static [&'static str] = &[
+    "TODO", "FIXME", "XXX", "SAFETY:", "HACK",
+];
+
+static SCHEMES: &'static [&'static str] = &[
+    "http://", "https://", "ftp://", "file://",
+];
+
+/// Returns whether `text` starts with the scheme of a URL which can be
+/// followed safely, such as `https://`.
+pub fn is_url(text: &str) -> bool {
+    SCHEMES.iter().any(|s| text.starts_with(*s))
+}
+
+// Returns whether a word may start at `i`.
+fn is_word_start(text: &str, i: uint) -> bool {
+    i == 0 || !is_ident_continue(text.char_at_reverse(i))
+}
+
+// Returns whether a word may end at `i`.
+fn is_word_end(text: &str, i: uint) -> bool {
+    i == text.len() || !is_ident_continue(text.char_at(i))
+}
+
+// Returns the end of the URL starting at `lo`. Trailing punctuation is left
+// out, and so is a closing parenthesis which has no opening one in the URL.
+fn url_end(text: &str, lo: uint) -> uint {
+    let rest = text.slice_from(lo);
+    let len = rest.find(|c: char| c.is_whitespace() || "<>\"'`".contains_char(c)).unwrap_or(rest.len());
+    let mut url = rest.slice_to(len);
+
+    loop {
+        let trimmed = url.trim_right_chars(&['.', ',', ';', ':', '!', '?']);
+        let trimmed = if trimmed.ends_with(")") && !trimmed.contains_char('(') {
+            trimmed.slice_to(trimmed.len() - 1)
+        } else {
+            trimmed
+        };
+        if trimmed.len() == url.len() {
+            break;
+        }
+        url = trimmed;
+    }
+
+    lo + url.len()
+}
+
+/// Splits a comment into the regions of its markers and URLs.
+pub fn split(text: &str, markers: &[~str]) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut plain = 0;
+
+    let mut i = 0;
+    while i < text.len() {
+        let rest = text.slice_from(i);
+        if !is_word_start(text, i) {
+            i = text.char_range_at(i).next;
+            continue;
+        }
+
+        // An empty marker would match everywhere without moving on.
+        let marker = markers.iter().find(|m| {
+            m.len() > 0 && rest.starts_with(m.as_slice()) &&
+                (!m.chars().last().map_or(false, is_ident_continue) || is_word_end(text, i + m.len()))
+        });
+        let (ty, end) = match marker {
+            Some(m) => ("marker", i + m.len()),
+            None if is_url(rest) => ("url", url_end(text, i)),
+            None => {
+                i = text.char_range_at(i).next;
+                continue;
+            }
+        };
+
+        if i > plain {
+            segments.push(Piece("", plain, i));
+        }
+        segments.push(Open(ty, i));
+        segments.push(Piece("", i, end));
+        segments.push(Close(ty, end));
+        plain = end;
+        i = end;
+    }
+    if text.len() > plain {
+        segments.push(Piece("", plain, text.len()));
+    }
+
+    segments
+}
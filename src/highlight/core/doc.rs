@@ -0,0 +1,243 @@
+// Highlighting of doc comments, whose text is Markdown.
+//
+// Headings, emphasis, code spans and links get their own classes. A fenced
+// code block usually spans many doc comment tokens: its lines are gathered
+// when the opening fence is seen, highlighted as Rust at once, and split back
+// into lines, which are emitted with their own doc comment.
+
+use std::cmp;
+
+use super::{Highlighter, Options, Start, End, Text};
+use super::comment;
+use super::lexer::is_ident_continue;
+use super::literal::{Segment, Open, Close, Piece};
+
+/// A part of a line of a code block. Offsets are relative to the line.
+#[deriving(Clone, Eq)]
+pub enum CodePart {
+    CodeStart(~str, uint),
+    CodeEnd(~str, uint),
+    CodeText(~str, uint, uint),
+}
+
+/// Returns the ranges of the lines of a doc comment, without the comment
+/// syntax: the `///` or `//!` and the space after it, and for block comments
+/// the delimiters and the `*` starting the lines.
+pub fn lines(text: &str) -> Vec<(uint, uint)> {
+    if text.starts_with("///") || text.starts_with("//!") {
+        let lo = if text.slice_from(3).starts_with(" ") { 4 } else { 3 };
+        return vec!((lo, text.len()));
+    }
+
+    let hi = if text.len() >= 5 && text.ends_with("*/") { text.len() - 2 } else { text.len() };
+    let mut lines = Vec::new();
+    let mut lo = 3;
+    for line in text.slice(3, hi).split('\n') {
+        let trimmed = line.trim_left();
+        let mut start = lo;
+        if lo > 3 && trimmed.starts_with("*") {
+            start = lo + line.len() - trimmed.len() + 1;
+            if text.slice(start, lo + line.len()).starts_with(" ") {
+                start += 1;
+            }
+        }
+        lines.push((start, lo + line.len()));
+        lo += line.len() + 1;
+    }
+
+    lines
+}
+
+/// Returns the info string of a code block fence (` ``` ` or `~~~`).
+pub fn fence<'a>(line: &'a str) -> Option<&'a str> {
+    let trimmed = line.trim_left();
+    if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+        let c = trimmed.char_at(0);
+        Some(trimmed.trim_left_chars(c).trim())
+    } else {
+        None
+    }
+}
+
+/// Returns whether a code block with this info string is Rust code, which is
+/// the case of those without a language, as in rustdoc.
+pub fn is_rust(info: &str) -> bool {
+    info.split(|c: char| c == ',' || c.is_whitespace()).filter(|t| t.len() > 0).all(|t| {
+        match t {
+            "rust" | "ignore" | "no_run" | "should_panic" | "compile_fail" |
+                "test_harness" | "standalone_crate" => true,
+            _ => t.starts_with("edition") || t.starts_with("ignore-"),
+        }
+    })
+}
+
+/// Highlights the lines of a code block, and splits the parts back into
+/// lines. Regions which go on after the end of a line are closed there, and
+/// opened again at the start of the next one.
+pub fn code(lines: &[&str], options: &Options) -> Vec<Vec<CodePart>> {
+    let code = lines.connect("\n");
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    for line in lines.iter() {
+        starts.push(offset);
+        offset += line.len() + 1;
+    }
+    let line_of = |offset: uint| starts.iter().rposition(|&start| start <= offset).unwrap_or(0);
+
+    let mut result: Vec<Vec<CodePart>> = lines.iter().map(|_| Vec::new()).collect();
+    let mut open: Vec<~str> = Vec::new();
+
+    for part in Highlighter::with_options(code.as_slice(), options.clone()) {
+        match part {
+            Start(ty, span) => {
+                let k = line_of(span.lo);
+                result.get_mut(k).push(CodeStart(ty.clone(), span.lo - *starts.get(k)));
+                open.push(ty);
+            }
+            End(ty, span) => {
+                let k = line_of(span.lo);
+                result.get_mut(k).push(CodeEnd(ty, span.lo - *starts.get(k)));
+                open.pop();
+            }
+            Text(ty, _, span) => {
+                let mut lo = span.lo;
+                loop {
+                    let k = line_of(lo);
+                    let start = *starts.get(k);
+                    let end = start + lines[k].len();
+                    let hi = cmp::min(span.hi, end);
+                    if hi > lo {
+                        result.get_mut(k).push(CodeText(ty.clone(), lo - start, hi - start));
+                    }
+                    if span.hi <= end {
+                        break;
+                    }
+
+                    for ty in open.iter().rev() {
+                        result.get_mut(k).push(CodeEnd(ty.clone(), end - start));
+                    }
+                    for ty in open.iter() {
+                        result.get_mut(k + 1).push(CodeStart(ty.clone(), 0));
+                    }
+                    lo = end + 1;
+                }
+            }
+        }
+    }
+
+    result
+}
+
+fn shift(segment: Segment, delta: uint) -> Segment {
+    match segment {
+        Open(ty, offset) => Open(ty, offset + delta),
+        Close(ty, offset) => Close(ty, offset + delta),
+        Piece(ty, lo, hi) => Piece(ty, lo + delta, hi + delta),
+    }
+}
+
+// Returns the end of the code span starting at `lo`.
+fn code_span(line: &str, lo: uint) -> Option<uint> {
+    let n = line.slice_from(lo).chars().take_while(|&c| c == '`').count();
+    let ticks = line.slice(lo, lo + n);
+    line.slice_from(lo + n).find_str(ticks).map(|i| lo + n + i + n)
+}
+
+// Returns the class and the end of the emphasis starting at `lo`. An `_`
+// inside a word, as in snake_case names, is no emphasis.
+fn emphasis(line: &str, lo: uint) -> Option<(&'static str, uint)> {
+    let c = line.char_at(lo);
+    if c == '_' && lo > 0 && is_ident_continue(line.char_at_reverse(lo)) {
+        return None;
+    }
+
+    let double = if c == '*' { "**" } else { "__" };
+    let n = if line.slice_from(lo).starts_with(double) { 2 } else { 1 };
+    let delimiter = line.slice(lo, lo + n);
+    let rest = line.slice_from(lo + n);
+    if rest.len() == 0 || rest.char_at(0).is_whitespace() {
+        return None;
+    }
+
+    match rest.find_str(delimiter) {
+        Some(i) if i > 0 => {
+            let ty = if n == 2 { "doc-strong" } else { "doc-emphasis" };
+            Some((ty, lo + n + i + n))
+        }
+        _ => None,
+    }
+}
+
+// Splits the link starting at `lo`, as `[text](target)` or `[text]`. The
+// target of inline links is in a `url` region if it is a URL as in other
+// comments, and not, for instance, an intra-doc path or a `javascript:` one.
+fn link(line: &str, lo: uint) -> Option<(Vec<Segment>, uint)> {
+    let close = match line.slice_from(lo).find(']') {
+        Some(i) => lo + i,
+        None => return None,
+    };
+    let rest = line.slice_from(close + 1);
+
+    match rest.find(')') {
+        Some(i) if rest.starts_with("(") && i > 1 => {
+            let (url_lo, url_hi) = (close + 2, close + 1 + i);
+            if !comment::is_url(line.slice(url_lo, url_hi)) {
+                return Some((vec!(Piece("doc-link", lo, url_hi + 1)), url_hi + 1));
+            }
+            let segments = vec!(
+                Open("doc-link", lo),
+                Piece("", lo, url_lo),
+                Open("url", url_lo),
+                Piece("", url_lo, url_hi),
+                Close("url", url_hi),
+                Piece("", url_hi, url_hi + 1),
+                Close("doc-link", url_hi + 1)
+            );
+            Some((segments, url_hi + 1))
+        }
+        _ => Some((vec!(Piece("doc-link", lo, close + 1)), close + 1)),
+    }
+}
+
+/// Splits a line of Markdown text. Plain text is split further into markers
+/// and URLs, as in other comments.
+pub fn markdown(line: &str, markers: &[~str]) -> Vec<Segment> {
+    let trimmed = line.trim_left();
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    if level >= 1 && level <= 6 && (trimmed.len() == level || trimmed.char_at(level) == ' ') {
+        return vec!(Piece("doc-heading", 0, line.len()));
+    }
+
+    let mut segments = Vec::new();
+    let flush = |segments: &mut Vec<Segment>, lo: uint, hi: uint| {
+        if hi > lo {
+            for segment in comment::split(line.slice(lo, hi), markers).move_iter() {
+                segments.push(shift(segment, lo));
+            }
+        }
+    };
+
+    let mut plain = 0;
+    let mut i = 0;
+    while i < line.len() {
+        let found = match line.char_at(i) {
+            '`' => code_span(line, i).map(|end| (vec!(Piece("doc-code", i, end)), end)),
+            '*' | '_' => emphasis(line, i).map(|(ty, end)| (vec!(Piece(ty, i, end)), end)),
+            '[' => link(line, i),
+            _ => None,
+        };
+
+        match found {
+            Some((found, end)) => {
+                flush(&mut segments, plain, i);
+                segments.push_all_move(found);
+                plain = end;
+                i = end;
+            }
+            None => i = line.char_range_at(i).next,
+        }
+    }
+    flush(&mut segments, plain, line.len());
+
+    segments
+}
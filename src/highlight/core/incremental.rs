@@ -0,0 +1,331 @@
+// Incremental re-highlighting, for editors which highlight a buffer on every
+// keystroke.
+//
+// A checkpoint is kept after every significant token. After an edit, the
+// source is highlighted again from a checkpoint a little before the edit, until
+// the fresh highlighter reaches a checkpoint which existed before the edit (at
+// the same place, shifted by the edit) with the same state. Since tokens are
+// always lexed whole, an edit opening a block comment or a raw string keeps
+// going until the comment or string ends, however far below that is.
+//
+// In rainbow mode, an edit may change which delimiters are matched anywhere in
+// the buffer, so the whole buffer is highlighted again.
+//
+// The lines of a code block in doc comments are highlighted together, so an
+// edit inside one restarts before its opening fence, and highlighting goes on
+// at least until its closing fence.
+
+use super::{Checkpoint, Highlighter, LineMap, Options, Part, Pos};
+
+/// The result of an edit: the parts covering `lo..old_hi` in the source
+/// before the edit are replaced by `parts`, which cover `lo..new_hi` in the
+/// source after the edit.
+///
+/// Parts after `old_hi` are unchanged, except that their offsets move by
+/// `new_hi - old_hi` and their positions as described by `old_end` and
+/// `new_end`.
+pub struct Change {
+    pub lo: uint,
+    pub old_hi: uint,
+    pub new_hi: uint,
+    pub old_end: Pos,
+    pub new_end: Pos,
+    pub parts: Vec<Part>,
+}
+
+pub struct IncrementalHighlighter {
+    src: ~str,
+    options: Options,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl IncrementalHighlighter {
+    /// Creates a highlighter for an empty buffer. Use `edit` with the initial
+    /// text to get its parts.
+    pub fn new() -> IncrementalHighlighter {
+        IncrementalHighlighter::with_options(Options::new())
+    }
+
+    pub fn with_options(options: Options) -> IncrementalHighlighter {
+        let src = "".to_owned();
+        let checkpoint = Highlighter::new(src.as_slice()).checkpoint().unwrap();
+
+        IncrementalHighlighter {
+            src: src,
+            options: options,
+            checkpoints: vec!(checkpoint),
+        }
+    }
+
+    pub fn source<'a>(&'a self) -> &'a str {
+        self.src.as_slice()
+    }
+
+    /// Replaces the bytes `lo..hi` of the buffer by `text`, and returns the
+    /// parts that need to be redrawn.
+    pub fn edit(&mut self, lo: uint, hi: uint, text: &str) -> Change {
+        assert!(lo <= hi && hi <= self.src.len());
+
+        let mut src = StrBuf::new();
+        src.push_str(self.src.slice_to(lo));
+        src.push_str(text);
+        src.push_str(self.src.slice_from(hi));
+        let src = src.into_owned();
+
+        let delta = text.len() as int - (hi - lo) as int;
+        let edit_end = lo + text.len();
+
+        // Restart a couple of tokens before the edit: the tokens right before
+        // it may be classified differently depending on what follows them.
+        let mut first = 0;
+        for (i, checkpoint) in self.checkpoints.iter().enumerate() {
+            if checkpoint.offset >= lo {
+                break;
+            }
+            first = i;
+        }
+        let rainbow = self.options.rainbow > 0;
+        let mut first = if first >= 2 && !rainbow { first - 2 } else { 0 };
+        match self.checkpoints.get(first).state.doc_code {
+            Some(ref code) => {
+                while first > 0 && self.checkpoints.get(first).offset > code.comment {
+                    first -= 1;
+                }
+            }
+            None => {}
+        }
+        let start = self.checkpoints.get(first).clone();
+
+        let mut parts = Vec::new();
+        let mut checkpoints = Vec::new();
+        let mut old = first + 1;
+        let mut resync = None;
+        let end;
+        {
+            let mut highlighter = Highlighter::resume(src.as_slice(), self.options.clone(), &start);
+            loop {
+                highlighter.step();
+                loop {
+                    match highlighter.queue.pop_front() {
+                        Some(part) => parts.push(part),
+                        None => break,
+                    }
+                }
+
+                let checkpoint = match highlighter.checkpoint() {
+                    Some(checkpoint) => checkpoint,
+                    None => {
+                        end = highlighter.map.pos(src.len());
+                        break;
+                    }
+                };
+
+                if checkpoint.offset >= edit_end && !rainbow && checkpoint.state.doc_code.is_none() {
+                    let target = checkpoint.offset as int - delta;
+                    while old < self.checkpoints.len() &&
+                        (self.checkpoints.get(old).offset as int) < target {
+                        old += 1;
+                    }
+
+                    if old < self.checkpoints.len() {
+                        let previous = self.checkpoints.get(old);
+                        if previous.offset as int == target && previous.state == checkpoint.state {
+                            end = checkpoint.pos.clone();
+                            resync = Some(old);
+                            break;
+                        }
+                    }
+                }
+
+                checkpoints.push(checkpoint);
+            }
+        }
+
+        let (old_hi, old_end) = match resync {
+            Some(i) => {
+                let previous = self.checkpoints.get(i);
+                (previous.offset, previous.pos.clone())
+            }
+            None => (self.src.len(), LineMap::new(self.src.as_slice()).pos(self.src.len())),
+        };
+        let new_hi = (old_hi as int + delta) as uint;
+
+        // Keep the checkpoints before the restart point, then the fresh ones,
+        // then the ones after the resynchronisation point, moved by the edit.
+        let mut updated = Vec::from_slice(self.checkpoints.slice_to(first + 1));
+        updated.push_all_move(checkpoints);
+        match resync {
+            Some(i) => {
+                for checkpoint in self.checkpoints.slice_from(i).iter() {
+                    let mut checkpoint = checkpoint.clone();
+                    checkpoint.offset = (checkpoint.offset as int + delta) as uint;
+                    checkpoint.pos = shift(&checkpoint.pos, &old_end, &end);
+                    match checkpoint.state.doc_code {
+                        Some(ref mut code) => {
+                            code.comment = (code.comment as int + delta) as uint;
+                            code.fence = (code.fence as int + delta) as uint;
+                        }
+                        None => {}
+                    }
+                    updated.push(checkpoint);
+                }
+            }
+            None => {}
+        }
+
+        self.src = src;
+        self.checkpoints = updated;
+
+        Change {
+            lo: start.offset,
+            old_hi: old_hi,
+            new_hi: new_hi,
+            old_end: old_end,
+            new_end: end,
+            parts: parts,
+        }
+    }
+}
+
+/// Moves a position after an edit which changed the end of the edited region
+/// from `old_end` to `new_end`.
+pub fn shift(pos: &Pos, old_end: &Pos, new_end: &Pos) -> Pos {
+    if pos.line == old_end.line {
+        Pos {
+            line: new_end.line,
+            col: pos.col - old_end.col + new_end.col,
+        }
+    } else {
+        Pos {
+            line: pos.line - old_end.line + new_end.line,
+            col: pos.col,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Change, IncrementalHighlighter, shift};
+    use super::super::{Highlighter, Options, Part, Start, End, Text, Span};
+
+    // Whether `part` is emitted by the step starting at `offset`, or by a
+    // later one: regions are ended after the token which ends them.
+    fn is_after(part: &Part, offset: uint) -> bool {
+        match *part {
+            Start(_, ref span) | Text(_, _, ref span) => span.lo >= offset,
+            End(..) => false,
+        }
+    }
+
+    // Checks that every region is ended, innermost first.
+    fn check_balance(parts: &[Part]) {
+        let mut open = Vec::new();
+        for part in parts.iter() {
+            match *part {
+                Start(ref ty, _) => open.push(ty.clone()),
+                End(ref ty, _) => assert_eq!(open.pop(), Some(ty.clone())),
+                Text(..) => {}
+            }
+        }
+        assert!(open.is_empty());
+    }
+
+    fn moved(span: &Span, change: &Change) -> Span {
+        let delta = change.new_hi as int - change.old_hi as int;
+        Span {
+            lo: (span.lo as int + delta) as uint,
+            hi: (span.hi as int + delta) as uint,
+            start: shift(&span.start, &change.old_end, &change.new_end),
+            end: shift(&span.end, &change.old_end, &change.new_end),
+        }
+    }
+
+    // An editor buffer, which keeps its parts up to date with the changes.
+    struct Buffer {
+        highlighter: IncrementalHighlighter,
+        options: Options,
+        parts: Vec<Part>,
+    }
+
+    impl Buffer {
+        fn new(options: Options) -> Buffer {
+            Buffer {
+                highlighter: IncrementalHighlighter::with_options(options.clone()),
+                options: options,
+                parts: Vec::new(),
+            }
+        }
+
+        // Applies an edit, and checks that the parts are those of the whole
+        // buffer highlighted again.
+        fn edit(&mut self, lo: uint, hi: uint, text: &str) {
+            let change = self.highlighter.edit(lo, hi, text);
+
+            let first = self.parts.iter().position(|p| is_after(p, change.lo)).unwrap_or(self.parts.len());
+            let last = self.parts.iter().position(|p| is_after(p, change.old_hi)).unwrap_or(self.parts.len());
+            let mut parts = Vec::from_slice(self.parts.slice_to(first));
+            parts.push_all(change.parts.as_slice());
+            for part in self.parts.slice_from(last).iter() {
+                parts.push(match *part {
+                    Start(ref ty, ref span) => Start(ty.clone(), moved(span, &change)),
+                    End(ref ty, ref span) => End(ty.clone(), moved(span, &change)),
+                    Text(ref ty, ref text, ref span) => Text(ty.clone(), text.clone(), moved(span, &change)),
+                });
+            }
+            self.parts = parts;
+
+            let src = self.highlighter.source();
+            let expected: Vec<Part> = Highlighter::with_options(src, self.options.clone()).collect();
+            assert_eq!(self.parts, expected);
+            check_balance(self.parts.as_slice());
+        }
+
+        // Replaces the first occurrence of `old`.
+        fn replace(&mut self, old: &str, new: &str) {
+            let lo = self.highlighter.source().find_str(old).unwrap();
+            self.edit(lo, lo + old.len(), new);
+        }
+    }
+
+    #[test]
+    fn block_comment() {
+        let mut buffer = Buffer::new(Options::new());
+        buffer.edit(0, 0, "fn f() {\n    let a = 1;\n}\n\nfn g() -> u8 {\n    let b = \"b\";\n    2\n}\n");
+        buffer.replace("let a", "/* let a");
+        buffer.replace("2\n}", "2 */\n}");
+        buffer.replace("/* let a", "let a");
+        buffer.replace("b = ", "b = /* c */ ");
+    }
+
+    #[test]
+    fn raw_string() {
+        let mut buffer = Buffer::new(Options::new());
+        buffer.edit(0, 0, "fn main() {\n    let s = \"a\";\n    let t = 1;\n    let u = \"#\";\n}\n");
+        buffer.replace("\"a\"", "r#\"a\"");
+        buffer.replace("1;", "1; \"#;");
+        buffer.replace("r#\"a\"", "\"a\"");
+    }
+
+    #[test]
+    fn doc_code_block() {
+        let mut buffer = Buffer::new(Options::new());
+        buffer.edit(0, 0, "/// Doc.\n///\n/// ```\n/// let a = 1;\n/// let b = \"b\";\n/// let c = 3;\n/// ```\nfn f() {}\n");
+        buffer.replace("let c = 3", "let c = \"3");
+        buffer.replace("let a = 1", "let a = 1 + a");
+        buffer.replace("\"3", "3");
+        buffer.replace("/// ```\nfn", "/// let d = 4;\n/// ```\nfn");
+        buffer.replace("```\n/// let a", "```text\n/// let a");
+    }
+
+    #[test]
+    fn nested_macro_rules() {
+        let mut buffer = Buffer::new(Options::new());
+        buffer.edit(0, 0, "macro_rules! outer {\n    ($name:ident) => {\n        macro_rules! $name {\n            ($x:expr) => { $x };\n        }\n    };\n}\n\nfn f() {}\n");
+        buffer.replace("$x };", "$x + 1 };");
+        buffer.replace("($x:expr)", "($x:expr, $y:expr)");
+        buffer.replace("        }\n    };", "    };");
+
+        let parts: Vec<Part> = Highlighter::new("unsafe { macro_rules! m { () => {").collect();
+        check_balance(parts.as_slice());
+    }
+}
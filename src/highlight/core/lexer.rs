@@ -0,0 +1,513 @@
+// A small, self-contained Rust tokenizer.
+//
+// The lexer is lossless: every byte of the source ends up in exactly one
+// token (whitespace and comments included), so concatenating the tokens gives
+// back the original text. It never fails either: unterminated literals and
+// comments, or characters that cannot start any token, are returned as tokens
+// flagged as `error` and lexing carries on after them.
+
+#[deriving(Clone, Eq, Show)]
+pub enum DocStyle {
+    NotDoc,
+    OuterDoc,
+    InnerDoc,
+}
+
+#[deriving(Clone, Eq, Show)]
+pub enum TokenKind {
+    Whitespace,
+    LineComment(DocStyle),
+    BlockComment(DocStyle),
+    Ident,
+    RawIdent,
+    Lifetime,
+    Char,
+    Byte,
+    Str,
+    RawStr,
+    ByteStr,
+    RawByteStr,
+    CStr,
+    RawCStr,
+    Number,
+    Punct,
+    Unknown,
+    // `#!/usr/bin/env ...` on the first line, newline included
+    Shebang,
+    // the `---` fenced block of cargo scripts, after the shebang if any
+    Frontmatter,
+}
+
+#[deriving(Clone, Eq, Show)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub lo: uint,
+    pub hi: uint,
+    pub error: bool,
+}
+
+impl Token {
+    pub fn is_comment(&self) -> bool {
+        match self.kind {
+            LineComment(..) | BlockComment(..) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        if self.error {
+            return false;
+        }
+
+        match self.kind {
+            Whitespace | LineComment(NotDoc) | BlockComment(NotDoc) => true,
+            _ => false,
+        }
+    }
+}
+
+// Multi-character punctuation, longest first.
+static PUNCTUATION: &'static [&'static str] = &[
+    "<<=", ">>=", "...", "..=",
+    "::", "->", "=>", "==", "!=", "<=", ">=", "&&", "||", "..",
+    "+=", "-=", "*=", "/=", "%=", "^=", "&=", "|=", "<<", ">>",
+];
+
+static SINGLE_PUNCTUATION: &'static str = ";,.()[]{}@#~?:$=!<>-&|+*/^%";
+
+pub fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+pub fn is_ident_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+// Returns the end of the shebang line starting `src`, newline included. A
+// `#!` followed by `[` starts an inner attribute instead, even with whitespace
+// or comments in between.
+fn shebang_end(src: &str) -> Option<uint> {
+    if !src.starts_with("#!") {
+        return None;
+    }
+
+    let mut rest = src.slice_from(2).trim_left();
+    loop {
+        if rest.starts_with("//") {
+            rest = rest.slice_from(rest.find('\n').unwrap_or(rest.len())).trim_left();
+        } else if rest.starts_with("/*") {
+            let mut depth = 0;
+            let mut i = 0;
+            while i < rest.len() {
+                if rest.slice_from(i).starts_with("/*") {
+                    depth += 1;
+                    i += 2;
+                } else if rest.slice_from(i).starts_with("*/") {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i = rest.char_range_at(i).next;
+                }
+            }
+            rest = rest.slice_from(i).trim_left();
+        } else {
+            break;
+        }
+    }
+    if rest.starts_with("[") {
+        return None;
+    }
+
+    Some(src.find('\n').map_or(src.len(), |i| i + 1))
+}
+
+#[deriving(Clone)]
+pub struct Lexer<'a> {
+    src: &'a str,
+    pos: uint,
+    error: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str) -> Lexer<'a> {
+        Lexer::resume(src, 0)
+    }
+
+    /// Starts lexing at `pos`, which must be a token boundary.
+    pub fn resume(src: &'a str, pos: uint) -> Lexer<'a> {
+        Lexer {
+            src: src,
+            pos: pos,
+            error: false,
+        }
+    }
+
+    pub fn pos(&self) -> uint {
+        self.pos
+    }
+
+    fn rest(&self) -> &'a str {
+        self.src.slice_from(self.pos)
+    }
+
+    fn nth_char(&self, n: uint) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        if self.pos >= self.src.len() {
+            return None;
+        }
+
+        let range = self.src.char_range_at(self.pos);
+        self.pos = range.next;
+        Some(range.ch)
+    }
+
+    fn bump_while(&mut self, f: |char| -> bool) {
+        loop {
+            match self.nth_char(0) {
+                Some(c) if f(c) => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    // Frontmatter may only start the source, or follow the shebang.
+    fn is_frontmatter_start(&self) -> bool {
+        self.rest().starts_with("---") &&
+            (self.pos == 0 ||
+             (self.src.slice_to(self.pos).ends_with("\n") && shebang_end(self.src) == Some(self.pos)))
+    }
+
+    // Lexes a frontmatter, which ends with a line made of the same number of
+    // dashes as the opening fence.
+    fn frontmatter(&mut self) -> TokenKind {
+        let rest = self.rest();
+        let fence = rest.slice_to(rest.chars().take_while(|&c| c == '-').count());
+
+        let mut offset = match rest.find('\n') {
+            Some(i) => i + 1,
+            None => rest.len(),
+        };
+        for line in rest.slice_from(offset).split('\n') {
+            if line.trim_right() == fence {
+                self.pos += offset + line.len();
+                return Frontmatter;
+            }
+            offset += line.len() + 1;
+        }
+
+        self.error = true;
+        self.pos = self.src.len();
+        Frontmatter
+    }
+
+    fn line_comment(&mut self) -> TokenKind {
+        // `///` is a doc comment, but `////` is not.
+        let style = match (self.nth_char(0), self.nth_char(1)) {
+            (Some('/'), Some('/')) => NotDoc,
+            (Some('/'), _) => OuterDoc,
+            (Some('!'), _) => InnerDoc,
+            _ => NotDoc,
+        };
+
+        self.bump_while(|c| c != '\n');
+        LineComment(style)
+    }
+
+    fn block_comment(&mut self) -> TokenKind {
+        // `/**` is a doc comment, but `/***` and `/**/` are not.
+        let style = match (self.nth_char(0), self.nth_char(1)) {
+            (Some('*'), Some('*')) | (Some('*'), Some('/')) => NotDoc,
+            (Some('*'), _) => OuterDoc,
+            (Some('!'), _) => InnerDoc,
+            _ => NotDoc,
+        };
+
+        let mut depth = 1;
+        while depth > 0 {
+            if self.rest().starts_with("/*") {
+                self.pos += 2;
+                depth += 1;
+            } else if self.rest().starts_with("*/") {
+                self.pos += 2;
+                depth -= 1;
+            } else if self.bump().is_none() {
+                self.error = true;
+                break;
+            }
+        }
+
+        BlockComment(style)
+    }
+
+    // Whether a raw string starts `skip` bytes after the current position,
+    // i.e. after its `r`.
+    fn is_raw_str_start(&self, skip: uint) -> bool {
+        let rest = self.rest().slice_from(skip);
+        let hashes = rest.chars().take_while(|&c| c == '#').count();
+        rest.slice_from(hashes).starts_with("\"")
+    }
+
+    // Whether the character before the current position, `c`, is the prefix
+    // of a literal such as `r"..."`, `b'.'`, `br"..."` or `c"..."`.
+    fn is_prefixed_literal(&self, c: char) -> bool {
+        match (c, self.nth_char(0)) {
+            ('r', _) => self.is_raw_str_start(0),
+            ('b', Some('\'')) | ('b', Some('"')) | ('c', Some('"')) => true,
+            ('b', Some('r')) | ('c', Some('r')) => self.is_raw_str_start(1),
+            _ => false,
+        }
+    }
+
+    fn prefixed_literal(&mut self, c: char) -> TokenKind {
+        match (c, self.nth_char(0)) {
+            ('r', _) => self.raw_str(RawStr),
+            ('b', Some('\'')) => {
+                self.bump();
+                self.quoted('\'');
+                Byte
+            }
+            ('b', Some('"')) => {
+                self.bump();
+                self.quoted('"');
+                ByteStr
+            }
+            ('b', _) => {
+                self.bump();
+                self.raw_str(RawByteStr)
+            }
+            ('c', Some('"')) => {
+                self.bump();
+                self.quoted('"');
+                CStr
+            }
+            _ => {
+                self.bump();
+                self.raw_str(RawCStr)
+            }
+        }
+    }
+
+    fn is_raw_ident_start(&self) -> bool {
+        match (self.nth_char(0), self.nth_char(1)) {
+            (Some('#'), Some(c)) => is_ident_start(c),
+            _ => false,
+        }
+    }
+
+    fn raw_str(&mut self, kind: TokenKind) -> TokenKind {
+        let mut hashes = 0;
+        while self.nth_char(0) == Some('#') {
+            self.bump();
+            hashes += 1;
+        }
+        self.bump();
+
+        loop {
+            match self.bump() {
+                Some('"') => {
+                    let closing = self.rest().chars().take_while(|&c| c == '#').count();
+                    if closing >= hashes {
+                        self.pos += hashes;
+                        break;
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    self.error = true;
+                    break;
+                }
+            }
+        }
+
+        kind
+    }
+
+    fn quoted(&mut self, quote: char) {
+        loop {
+            // Character literals never span several lines, so don't let a
+            // stray quote swallow the rest of the file.
+            if quote == '\'' && self.nth_char(0) == Some('\n') {
+                self.error = true;
+                break;
+            }
+
+            match self.bump() {
+                Some('\\') => {
+                    self.bump();
+                }
+                Some(c) if c == quote => break,
+                Some(_) => {}
+                None => {
+                    self.error = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn char_or_lifetime(&mut self) -> TokenKind {
+        match (self.nth_char(0), self.nth_char(1)) {
+            // A raw lifetime or label, as `'r#try`.
+            (Some('r'), Some('#')) if self.nth_char(2).map_or(false, is_ident_start) => {
+                self.pos += 2;
+                self.bump_while(is_ident_continue);
+                Lifetime
+            }
+            (Some(c), next) if is_ident_start(c) && next != Some('\'') => {
+                self.bump_while(is_ident_continue);
+                Lifetime
+            }
+            _ => {
+                self.quoted('\'');
+                Char
+            }
+        }
+    }
+
+    fn number(&mut self, first: char) -> TokenKind {
+        let radix = match (first, self.nth_char(0)) {
+            ('0', Some('x')) => 16,
+            ('0', Some('o')) => 8,
+            ('0', Some('b')) => 2,
+            _ => 10,
+        };
+
+        if radix != 10 {
+            self.bump();
+            let start = self.pos;
+            self.bump_while(|c| c == '_' || c.is_digit_radix(16));
+            if self.src.slice(start, self.pos).chars().all(|c| c == '_') {
+                self.error = true;
+            }
+        } else {
+            self.bump_while(|c| c == '_' || c.is_digit());
+
+            // A fractional part, but not a range (`1..2`) or a method call
+            // on an integer (`1.foo()`).
+            match (self.nth_char(0), self.nth_char(1)) {
+                (Some('.'), Some(c)) if c == '.' || is_ident_start(c) => {}
+                (Some('.'), _) => {
+                    self.bump();
+                    self.bump_while(|c| c == '_' || c.is_digit());
+                }
+                _ => {}
+            }
+
+            let exponent = match (self.nth_char(0), self.nth_char(1), self.nth_char(2)) {
+                (Some('e'), Some(c), _) | (Some('E'), Some(c), _) if c.is_digit() => 1,
+                (Some('e'), Some('+'), Some(c)) | (Some('e'), Some('-'), Some(c)) |
+                    (Some('E'), Some('+'), Some(c)) | (Some('E'), Some('-'), Some(c))
+                    if c.is_digit() => 2,
+                _ => 0,
+            };
+            if exponent > 0 {
+                self.pos += exponent;
+                self.bump_while(|c| c == '_' || c.is_digit());
+            }
+        }
+
+        // Type suffix, e.g. `u8` or `f64`.
+        self.bump_while(is_ident_continue);
+
+        Number
+    }
+
+    fn punct(&mut self, lo: uint) -> TokenKind {
+        let rest = self.src.slice_from(lo);
+        for p in PUNCTUATION.iter() {
+            if rest.starts_with(*p) {
+                self.pos = lo + p.len();
+                return Punct;
+            }
+        }
+
+        if SINGLE_PUNCTUATION.contains_char(rest.char_at(0)) {
+            Punct
+        } else {
+            self.error = true;
+            Unknown
+        }
+    }
+}
+
+impl<'a> Iterator<Token> for Lexer<'a> {
+    fn next(&mut self) -> Option<Token> {
+        let lo = self.pos;
+        self.error = false;
+
+        let special = if lo == 0 && shebang_end(self.src).is_some() {
+            self.pos = shebang_end(self.src).unwrap();
+            Some(Shebang)
+        } else if self.is_frontmatter_start() {
+            Some(self.frontmatter())
+        } else {
+            None
+        };
+        match special {
+            Some(kind) => {
+                return Some(Token {
+                    kind: kind,
+                    lo: lo,
+                    hi: self.pos,
+                    error: self.error,
+                });
+            }
+            None => {}
+        }
+
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return None,
+        };
+
+        let kind = match c {
+            c if c.is_whitespace() => {
+                self.bump_while(|c| c.is_whitespace());
+                Whitespace
+            }
+            '/' => match self.nth_char(0) {
+                Some('/') => {
+                    self.bump();
+                    self.line_comment()
+                }
+                Some('*') => {
+                    self.bump();
+                    self.block_comment()
+                }
+                _ => self.punct(lo),
+            },
+            'r' | 'b' | 'c' if self.is_prefixed_literal(c) => self.prefixed_literal(c),
+            'r' if self.is_raw_ident_start() => {
+                self.bump();
+                self.bump_while(is_ident_continue);
+                RawIdent
+            }
+            c if is_ident_start(c) => {
+                self.bump_while(is_ident_continue);
+                Ident
+            }
+            '0'..'9' => self.number(c),
+            '\'' => self.char_or_lifetime(),
+            '"' => {
+                self.quoted('"');
+                Str
+            }
+            _ => self.punct(lo),
+        };
+
+        Some(Token {
+            kind: kind,
+            lo: lo,
+            hi: self.pos,
+            error: self.error,
+        })
+    }
+}
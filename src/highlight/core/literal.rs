@@ -0,0 +1,277 @@
+// Splitting of literals into sub-parts.
+//
+// Offsets in segments are relative to the start of the literal, and the
+// segments cover the literal without gaps. Text which has no class of its own
+// is given the empty class, and takes the one of the enclosing literal.
+
+use std::num;
+
+use super::lexer::{is_ident_start, is_ident_continue};
+
+pub enum Segment {
+    Open(&'static str, uint),
+    Close(&'static str, uint),
+    Piece(&'static str, uint, uint),
+}
+
+// Macros taking a format string, and the index of that string among their
+// arguments.
+static FORMAT_MACROS: &'static [(&'static str, uint)] = &[
+    ("format", 0), ("format_args", 0), ("print", 0), ("println", 0),
+    ("eprint", 0), ("eprintln", 0), ("write", 1), ("writeln", 1),
+    ("panic", 0), ("unreachable", 0), ("todo", 0), ("unimplemented", 0),
+    ("assert", 1), ("assert_eq", 2), ("assert_ne", 2),
+    ("debug_assert", 1), ("debug_assert_eq", 2), ("debug_assert_ne", 2),
+    ("trace", 0), ("debug", 0), ("info", 0), ("warn", 0), ("error", 0),
+];
+
+/// Finds the format string among the arguments of a format macro call.
+#[deriving(Clone, Eq)]
+pub struct FormatCall {
+    // index of the format string argument
+    arg: uint,
+    index: uint,
+    // delimiter depth, 0 until the arguments are opened
+    depth: uint,
+}
+
+impl FormatCall {
+    /// Returns a `FormatCall` if `name` is a format macro.
+    pub fn new(name: &str) -> Option<FormatCall> {
+        FORMAT_MACROS.iter().find(|&&(m, _)| m == name).map(|&(_, arg)| {
+            FormatCall {
+                arg: arg,
+                index: 0,
+                depth: 0,
+            }
+        })
+    }
+
+    /// Handles the next significant token of the call, starting with the `!`.
+    /// Returns whether the token is the format string, and whether the call
+    /// is over, which is at its closing delimiter: the arguments after the
+    /// format string may hold other calls.
+    pub fn push(&mut self, tok: &str, is_str: bool) -> (bool, bool) {
+        match tok {
+            "!" if self.depth == 0 => (false, false),
+            "(" | "[" | "{" => {
+                self.depth += 1;
+                (false, false)
+            }
+            _ if self.depth == 0 => (false, true),
+            ")" | "]" | "}" => {
+                self.depth -= 1;
+                (false, self.depth == 0)
+            }
+            "," if self.depth == 1 => {
+                self.index += 1;
+                (false, false)
+            }
+            _ if is_str && self.depth == 1 && self.index == self.arg => (true, false),
+            _ => (false, false),
+        }
+    }
+}
+
+struct Builder {
+    segments: Vec<Segment>,
+    // start of the text not yet put in a segment
+    plain: uint,
+}
+
+impl Builder {
+    fn flush(&mut self, to: uint) {
+        if to > self.plain {
+            self.segments.push(Piece("", self.plain, to));
+        }
+        self.plain = to;
+    }
+
+    fn piece(&mut self, ty: &'static str, lo: uint, hi: uint) {
+        self.flush(lo);
+        self.segments.push(Piece(ty, lo, hi));
+        self.plain = hi;
+    }
+}
+
+/// Splits a string or character literal (quotes and prefix included), so that
+/// escape sequences are in `escape` regions, or `escape-invalid` ones. When
+/// `format` is set, the placeholders and brace escapes of a format string are
+/// split out too.
+pub fn string(text: &str, format: bool) -> Vec<Segment> {
+    let mut builder = Builder {
+        segments: Vec::new(),
+        plain: 0,
+    };
+
+    let open = match text.find(|c: char| c == '"' || c == '\'') {
+        Some(open) => open,
+        None => return vec!(Piece("", 0, text.len())),
+    };
+    let prefix = text.slice_to(open);
+    // Raw strings end with as many hashes as they start with.
+    let hashes = prefix.chars().filter(|&c| c == '#').count();
+    let raw = prefix.contains_char('r');
+    let is_str = text.char_at(open) == '"';
+    let hi = text.len() - 1 - hashes;
+
+    let mut i = open + 1;
+    while i < hi {
+        let c = text.char_at(i);
+        let rest = text.slice(i, hi);
+
+        if c == '\\' && !raw {
+            let (end, valid) = escape(text, i, prefix, is_str);
+            let ty = if valid { "escape" } else { "escape-invalid" };
+            builder.flush(i);
+            builder.segments.push(Open(ty, i));
+            builder.piece("", i, end);
+            builder.segments.push(Close(ty, end));
+            i = end;
+        } else if format && (rest.starts_with("{{") || rest.starts_with("}}")) {
+            builder.piece("format-escape", i, i + 2);
+            i += 2;
+        } else if format && c == '{' {
+            i = placeholder(&mut builder, text, i, hi);
+        } else {
+            i = text.char_range_at(i).next;
+        }
+    }
+    builder.flush(text.len());
+
+    builder.segments
+}
+
+/// Splits a number literal into its `number-prefix` (`0x`), `number-digits`
+/// (fractional part included), `number-exponent` (`e-9`) and `number-suffix`
+/// (`u8`) parts.
+pub fn number(text: &str) -> Vec<Segment> {
+    let mut builder = Builder {
+        segments: Vec::new(),
+        plain: 0,
+    };
+
+    let (radix, start) = if text.starts_with("0x") {
+        (16, 2)
+    } else if text.starts_with("0o") {
+        (8, 2)
+    } else if text.starts_with("0b") {
+        (2, 2)
+    } else {
+        (10, 0)
+    };
+    if start > 0 {
+        builder.piece("number-prefix", 0, start);
+    }
+
+    // Octal and binary literals with out-of-range digits still have them
+    // highlighted as digits.
+    let digit_radix = if radix == 16 { 16 } else { 10 };
+    let mut i = scan(text, start, |c| c == '_' || c.is_digit_radix(digit_radix));
+    if radix == 10 && text.slice_from(i).starts_with(".") {
+        i = scan(text, i + 1, |c| c == '_' || c.is_digit());
+    }
+    if i > start {
+        builder.piece("number-digits", start, i);
+    }
+
+    if radix == 10 && (text.slice_from(i).starts_with("e") || text.slice_from(i).starts_with("E")) {
+        let mut end = i + 1;
+        if text.slice_from(end).starts_with("+") || text.slice_from(end).starts_with("-") {
+            end += 1;
+        }
+        let end = scan(text, end, |c| c == '_' || c.is_digit());
+        builder.piece("number-exponent", i, end);
+        i = end;
+    }
+
+    if i < text.len() {
+        builder.piece("number-suffix", i, text.len());
+    }
+
+    builder.segments
+}
+
+fn scan(text: &str, lo: uint, f: |char| -> bool) -> uint {
+    let len = text.slice_from(lo).chars().take_while(|&c| f(c)).fold(0, |len, c| len + c.len_utf8_bytes());
+    lo + len
+}
+
+// Returns the end of the escape sequence starting at `lo` (on its backslash),
+// and whether it is valid. Only byte and C string literals allow non-ASCII
+// `\x` escapes, byte literals don't allow `\u` escapes, and line
+// continuations are only allowed in strings.
+fn escape(text: &str, lo: uint, prefix: &str, is_str: bool) -> (uint, bool) {
+    let byte = prefix.contains_char('b');
+    let any_byte = byte || prefix.contains_char('c');
+    let rest = text.slice_from(lo + 1);
+    let c = match rest.chars().next() {
+        Some(c) => c,
+        None => return (text.len(), false),
+    };
+
+    match c {
+        'n' | 'r' | 't' | '\\' | '0' | '\'' | '"' => (lo + 2, true),
+        'x' => {
+            let digits = rest.slice_from(1).chars().take(2).take_while(|c| c.is_digit_radix(16)).count();
+            let value = num::from_str_radix::<uint>(rest.slice(1, 1 + digits), 16).unwrap_or(0);
+            (lo + 2 + digits, digits == 2 && (any_byte || value <= 0x7f))
+        }
+        'u' => {
+            if !rest.slice_from(1).starts_with("{") {
+                return (lo + 2, false);
+            }
+
+            let (len, end) = match rest.find('}') {
+                Some(close) if !rest.slice(2, close).contains_char('\n') => (close - 2, close + 1),
+                _ => return (lo + 3, false),
+            };
+            let digits: StrBuf = rest.slice(2, 2 + len).chars().filter(|&c| c != '_').collect();
+            let value = num::from_str_radix::<u32>(digits.as_slice(), 16);
+            let valid = !byte && digits.len() > 0 && digits.len() <= 6 &&
+                value.map_or(false, |v| v <= 0x10FFFF && !(v >= 0xD800 && v <= 0xDFFF));
+            (lo + 1 + end, valid)
+        }
+        '\n' | '\r' if is_str => {
+            // A line continuation also skips the whitespace that follows.
+            let skipped = rest.chars().take_while(|c| c.is_whitespace()).count();
+            let len = rest.chars().take(skipped).fold(0, |len, c| len + c.len_utf8_bytes());
+            (lo + 1 + len, true)
+        }
+        c => (lo + 1 + c.len_utf8_bytes(), false),
+    }
+}
+
+// Splits the placeholder starting at `lo` (on its `{`) into its argument and
+// its format spec, and returns its end.
+fn placeholder(builder: &mut Builder, text: &str, lo: uint, hi: uint) -> uint {
+    let end = match text.slice(lo, hi).find('}') {
+        Some(end) => lo + end + 1,
+        None => return hi,
+    };
+
+    builder.flush(lo);
+    builder.segments.push(Open("format-placeholder", lo));
+    builder.piece("", lo, lo + 1);
+
+    let inner = text.slice(lo + 1, end - 1);
+    let arg = inner.find(':').unwrap_or(inner.len());
+    let name = inner.slice_to(arg).trim();
+    if name.len() > 0 {
+        let start = lo + 1 + inner.find_str(name).unwrap();
+        let ty = match name.char_at(0) {
+            c if is_ident_start(c) && name.chars().all(is_ident_continue) => "ident",
+            c if c.is_digit() => "number",
+            _ => "",
+        };
+        builder.piece(ty, start, start + name.len());
+    }
+    if arg < inner.len() {
+        builder.piece("format-spec", lo + 1 + arg, end - 1);
+    }
+
+    builder.piece("", end - 1, end);
+    builder.segments.push(Close("format-placeholder", end));
+
+    end
+}
@@ -0,0 +1,196 @@
+// Highlighting of `macro_rules!` definitions.
+//
+// Once `macro_rules!` has been seen, every significant token of the definition
+// is pushed here. The rules are split into `macro-matcher` and
+// `macro-transcriber` regions, and metavariables, fragment specifiers,
+// repetition groups and their separators get their own classes.
+
+use super::lexer::is_ident_start;
+
+#[deriving(Clone, Eq)]
+enum Metavar {
+    NoMetavar,
+    // after `$`
+    Dollar,
+    // after `$name` in a matcher, which may be followed by `:fragment`
+    Name,
+    // after `$name:`
+    Colon,
+    // after the `$` of `$(`
+    Group,
+}
+
+#[deriving(Clone, Eq)]
+enum RepetitionEnd {
+    NoRepetition,
+    // after the `)` closing a repetition group
+    Closed,
+    // after the separator of a repetition group
+    Separated,
+}
+
+/// What to do with a token of a macro definition.
+pub struct Step {
+    /// A region to start before the token.
+    pub start: Option<&'static str>,
+    /// The class of the token, if it isn't highlighted as usual.
+    pub class: Option<&'static str>,
+    /// A region to end after the token.
+    pub end: Option<&'static str>,
+    /// Whether the token ends the definition.
+    pub done: bool,
+}
+
+#[deriving(Clone, Eq)]
+pub struct MacroRules {
+    // delimiter depth, 0 until the body of the definition is opened
+    depth: uint,
+    // whether the next rule part at depth 1 is a matcher
+    matcher: bool,
+    part: Option<&'static str>,
+    // depth inside each open repetition group
+    repetitions: Vec<uint>,
+    metavar: Metavar,
+    repetition_end: RepetitionEnd,
+}
+
+fn is_ident(tok: &str) -> bool {
+    tok.chars().next().map_or(false, is_ident_start)
+}
+
+impl MacroRules {
+    pub fn new() -> MacroRules {
+        MacroRules {
+            depth: 0,
+            matcher: true,
+            part: None,
+            repetitions: Vec::new(),
+            metavar: NoMetavar,
+            repetition_end: NoRepetition,
+        }
+    }
+
+    /// Handles the next significant token `tok` of the definition, followed
+    /// by `next`.
+    pub fn push(&mut self, tok: &str, next: &str) -> Step {
+        let mut step = Step {
+            start: None,
+            class: None,
+            end: None,
+            done: false,
+        };
+
+        // `macro_rules! name {`, or `macro_rules! $name {` for a definition
+        // inside the transcriber of another one
+        if self.depth == 0 {
+            match (self.metavar, tok) {
+                (Dollar, _) => {
+                    self.metavar = NoMetavar;
+                    step.class = Some("macro-nonterminal");
+                }
+                (_, "!") => {}
+                (_, "$") if is_ident(next) => {
+                    self.metavar = Dollar;
+                    step.class = Some("macro-nonterminal");
+                }
+                (_, "(") | (_, "[") | (_, "{") => self.depth = 1,
+                _ if is_ident(tok) => step.class = Some("macro"),
+                _ => step.done = true,
+            }
+            return step;
+        }
+
+        // The repetition operator of a group, maybe after a separator.
+        match (self.repetition_end, tok) {
+            (NoRepetition, _) => {}
+            (_, "*") | (_, "+") | (_, "?") => {
+                self.repetition_end = NoRepetition;
+                step.class = Some("macro-repetition");
+                return step;
+            }
+            (Closed, _) if !is_delimiter(tok) => {
+                self.repetition_end = Separated;
+                step.class = Some("macro-separator");
+                return step;
+            }
+            _ => self.repetition_end = NoRepetition,
+        }
+
+        match (self.metavar, tok) {
+            (NoMetavar, "$") if next == "(" => {
+                self.metavar = Group;
+                step.class = Some("macro-repetition");
+                return step;
+            }
+            (NoMetavar, "$") if is_ident(next) => {
+                self.metavar = Dollar;
+                step.class = Some("macro-nonterminal");
+                return step;
+            }
+            (Dollar, _) => {
+                let in_matcher = self.part == Some("macro-matcher");
+                self.metavar = if in_matcher && next == ":" { Name } else { NoMetavar };
+                step.class = Some("macro-nonterminal");
+                return step;
+            }
+            (Name, ":") => {
+                self.metavar = Colon;
+                step.class = Some("macro-fragment");
+                return step;
+            }
+            (Colon, _) => {
+                self.metavar = NoMetavar;
+                step.class = Some("macro-fragment");
+                return step;
+            }
+            (Group, "(") => {
+                self.metavar = NoMetavar;
+                self.depth += 1;
+                self.repetitions.push(self.depth);
+                step.class = Some("macro-repetition");
+                return step;
+            }
+            _ => self.metavar = NoMetavar,
+        }
+
+        match tok {
+            "(" | "[" | "{" => {
+                if self.depth == 1 {
+                    let part = if self.matcher {
+                        "macro-matcher"
+                    } else {
+                        "macro-transcriber"
+                    };
+                    self.matcher = !self.matcher;
+                    self.part = Some(part);
+                    step.start = Some(part);
+                }
+                self.depth += 1;
+            }
+            ")" | "]" | "}" => {
+                if self.repetitions.last() == Some(&self.depth) {
+                    self.repetitions.pop();
+                    self.repetition_end = Closed;
+                    step.class = Some("macro-repetition");
+                }
+
+                self.depth -= 1;
+                if self.depth == 1 {
+                    step.end = self.part.take();
+                } else if self.depth == 0 {
+                    step.done = true;
+                }
+            }
+            _ => {}
+        }
+
+        step
+    }
+}
+
+fn is_delimiter(tok: &str) -> bool {
+    match tok {
+        "(" | ")" | "[" | "]" | "{" | "}" => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,1249 @@
+use collections::{Deque, HashSet, RingBuf};
+
+use self::lexer::{Lexer, Token};
+use self::lexer::{Ident, RawIdent, Lifetime, Number, Punct, Unknown, Shebang, Frontmatter};
+use self::lexer::{Char, Byte, Str, RawStr, ByteStr, RawByteStr, CStr, RawCStr};
+use self::doc::{CodePart, CodeStart, CodeEnd, CodeText};
+use self::lexer::{Whitespace, LineComment, BlockComment, InnerDoc};
+use self::literal::{FormatCall, Segment, Open, Close, Piece};
+use self::macros::MacroRules;
+use self::names::Names;
+use self::scope::Nesting;
+use self::semantic::Semantic;
+use self::variables::Variables;
+
+pub mod comment;
+pub mod doc;
+pub mod incremental;
+pub mod lexer;
+pub mod literal;
+pub mod macros;
+pub mod range;
+pub mod names;
+pub mod scope;
+pub mod semantic;
+pub mod toml;
+pub mod tree;
+pub mod variables;
+
+/// A position in the source: `line` starts at 1, `col` is the number of
+/// characters since the start of the line.
+#[deriving(Clone, Eq, Encodable, Show)]
+pub struct Pos {
+    pub line: uint,
+    pub col: uint,
+}
+
+/// A region of the source, as a byte range and as line/column positions.
+#[deriving(Clone, Eq, Encodable, Show)]
+pub struct Span {
+    pub lo: uint,
+    pub hi: uint,
+    pub start: Pos,
+    pub end: Pos,
+}
+
+/// `Start` and `End` carry the empty span where the region begins or ends,
+/// `Text` the span of its text.
+#[deriving(Clone, Eq, Encodable, Show)]
+pub enum Part {
+    Start(~str, Span),
+    End(~str, Span),
+    Text(~str, ~str, Span),
+}
+
+impl Part {
+    pub fn span<'a>(&'a self) -> &'a Span {
+        match *self {
+            Start(_, ref span) | End(_, ref span) | Text(_, _, ref span) => span,
+        }
+    }
+}
+
+// Turns byte offsets into spans. Offsets are expected to be mostly increasing,
+// which is the order parts are produced in, so the lookup just walks forward
+// from the previous one.
+struct LineMap<'a> {
+    src: &'a str,
+    offset: uint,
+    pos: Pos,
+}
+
+impl<'a> LineMap<'a> {
+    fn new(src: &'a str) -> LineMap<'a> {
+        LineMap::resume(src, 0, Pos { line: 1, col: 0 })
+    }
+
+    fn resume(src: &'a str, offset: uint, pos: Pos) -> LineMap<'a> {
+        LineMap {
+            src: src,
+            offset: offset,
+            pos: pos,
+        }
+    }
+
+    fn pos(&mut self, offset: uint) -> Pos {
+        if offset < self.offset {
+            self.offset = 0;
+            self.pos = Pos { line: 1, col: 0 };
+        }
+
+        for c in self.src.slice(self.offset, offset).chars() {
+            if c == '\n' {
+                self.pos.line += 1;
+                self.pos.col = 0;
+            } else {
+                self.pos.col += 1;
+            }
+        }
+        self.offset = offset;
+
+        self.pos.clone()
+    }
+
+    fn span(&mut self, lo: uint, hi: uint) -> Span {
+        let start = self.pos(lo);
+        let end = self.pos(hi);
+
+        Span {
+            lo: lo,
+            hi: hi,
+            start: start,
+            end: end,
+        }
+    }
+}
+
+#[deriving(Clone, Eq, Show)]
+pub enum Edition {
+    Edition2015,
+    Edition2018,
+    Edition2021,
+    Edition2024,
+}
+
+impl Edition {
+    pub fn year(&self) -> uint {
+        match *self {
+            Edition2015 => 2015,
+            Edition2018 => 2018,
+            Edition2021 => 2021,
+            Edition2024 => 2024,
+        }
+    }
+}
+
+impl FromStr for Edition {
+    fn from_str(s: &str) -> Option<Edition> {
+        match s {
+            "2015" => Some(Edition2015),
+            "2018" => Some(Edition2018),
+            "2021" => Some(Edition2021),
+            "2024" => Some(Edition2024),
+            _ => None,
+        }
+    }
+}
+
+/// Settings of a `Highlighter`.
+#[deriving(Clone)]
+pub struct Options {
+    pub edition: Edition,
+    /// Classify identifiers by their syntactic position (type, function,
+    /// field...) instead of using `ident` for all of them.
+    pub semantic: bool,
+    /// Classes of well-known names, on top of the prelude of `edition`.
+    pub names: Names,
+    /// Give delimiters a `delim-N` class, where `N` is their nesting depth
+    /// modulo `rainbow`, and `delim-unmatched` to those without a match. Zero
+    /// disables it.
+    pub rainbow: uint,
+    /// Give each lifetime a `lifetime-N` class, where `N` is the order of its
+    /// first appearance modulo `lifetimes`. `'static` and `'_` get the
+    /// `lifetime-static` and `lifetime-anon` classes. Zero disables it.
+    pub lifetimes: uint,
+    /// Give each local variable a `var-N` class, where `N` is the order of
+    /// its declaration in the enclosing function modulo `variables`. Zero
+    /// disables it.
+    pub variables: uint,
+    /// Words highlighted as `marker` in comments, `TODO` and the like by
+    /// default.
+    pub markers: Vec<~str>,
+}
+
+impl Options {
+    pub fn new() -> Options {
+        Options {
+            edition: Edition2021,
+            semantic: false,
+            names: Names::new(),
+            rainbow: 0,
+            lifetimes: 0,
+            variables: 0,
+            markers: comment::MARKERS.iter().map(|m| m.to_owned()).collect(),
+        }
+    }
+}
+
+static KEYWORDS: &'static [&'static str] = &[
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+    "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct",
+    "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+    // reserved
+    "abstract", "become", "box", "do", "final", "macro", "override", "priv",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+// Keywords introduced by an edition, and the year of that edition. They are
+// plain identifiers in older editions.
+static EDITION_KEYWORDS: &'static [(&'static str, uint)] = &[
+    ("async", 2018), ("await", 2018), ("dyn", 2018), ("try", 2018),
+    ("gen", 2024),
+];
+
+pub fn is_keyword(name: &str, edition: Edition) -> bool {
+    KEYWORDS.iter().any(|kw| *kw == name) ||
+        EDITION_KEYWORDS.iter().any(|&(kw, year)| kw == name && edition.year() >= year)
+}
+
+// Tokens after which an item or a statement may start: `unsafe fn` there is a
+// declaration, and elsewhere a function pointer type.
+static ITEM_PREV: &'static [&'static str] = &[
+    "{", "}", ";", "]", ")", "pub", "const", "async", "default",
+];
+
+/// Highlights the whole source at once, with the default options.
+///
+/// This is a shortcut for collecting a `Highlighter`; prefer iterating over
+/// the highlighter directly for large inputs.
+pub fn highlight(src: &str) -> Vec<Part> {
+    Highlighter::new(src).collect()
+}
+
+/// Returns the offset of the first byte where the texts of `parts` differ
+/// from `src`, or `None` if they make up `src` exactly.
+///
+/// The parts of a `Highlighter` always make up their source, as every byte is
+/// lexed into exactly one token; this checks it. `src` is taken as bytes, so
+/// that a file which isn't valid UTF-8 differs from the parts of its lossy
+/// conversion where its first invalid sequence is.
+pub fn check<T: Iterator<Part>>(src: &[u8], mut parts: T) -> Option<uint> {
+    let mut offset = 0;
+
+    for part in parts {
+        match part {
+            Text(_, ref text, _) => {
+                for (i, &b) in text.as_bytes().iter().enumerate() {
+                    if offset + i >= src.len() || src[offset + i] != b {
+                        return Some(offset + i);
+                    }
+                }
+                offset += text.len();
+            }
+            _ => {}
+        }
+    }
+
+    if offset < src.len() {
+        Some(offset)
+    } else {
+        None
+    }
+}
+
+// Returns the next token which is not whitespace or a plain comment.
+fn next_significant(lexer: &mut Lexer) -> Option<Token> {
+    loop {
+        match lexer.next() {
+            Some(ref tok) if tok.is_trivia() => {}
+            tok => return tok,
+        }
+    }
+}
+
+// Returns the offsets of the delimiters which have no match. A closing
+// delimiter which doesn't match the innermost open one closes the nearest one
+// it matches, leaving those in between unmatched.
+fn unmatched_delimiters(src: &str) -> HashSet<uint> {
+    let mut unmatched = HashSet::new();
+    let mut open: Vec<(char, uint)> = Vec::new();
+
+    for tok in Lexer::new(src) {
+        if tok.kind != Punct || tok.hi != tok.lo + 1 {
+            continue;
+        }
+
+        let c = src.char_at(tok.lo);
+        let opening = match c {
+            '(' | '[' | '{' => {
+                open.push((c, tok.lo));
+                continue;
+            }
+            ')' => '(',
+            ']' => '[',
+            '}' => '{',
+            _ => continue,
+        };
+
+        match open.iter().rposition(|&(o, _)| o == opening) {
+            Some(i) => {
+                for &(_, lo) in open.slice_from(i + 1).iter() {
+                    unmatched.insert(lo);
+                }
+                open.truncate(i);
+            }
+            None => {
+                unmatched.insert(tok.lo);
+            }
+        }
+    }
+
+    for &(_, lo) in open.iter() {
+        unmatched.insert(lo);
+    }
+
+    unmatched
+}
+
+// Everything the highlighter carries from one token to the next. Two
+// highlighters at the same offset with equal states produce the same parts
+// from there on.
+#[deriving(Clone, Eq)]
+struct State {
+    // classes of the attribute, unsafe block and macro rule regions open,
+    // innermost last
+    regions: Vec<&'static str>,
+    // class of the attribute being highlighted, and its bracket depth
+    attribute: Option<&'static str>,
+    attribute_depth: uint,
+    is_macro: bool,
+    is_macro_nonterminal: bool,
+    // set after `break` and `continue`, which may be followed by a label
+    is_label: bool,
+    // `macro_rules!` definitions being highlighted, innermost last: helper
+    // macros may be defined in the transcriber of another one
+    macro_rules: Vec<MacroRules>,
+    // calls of macros taking a format string being highlighted, innermost
+    // last; the tokens of other macro calls go to the enclosing call
+    format_calls: Vec<FormatCall>,
+    // number of matched delimiters open, in rainbow mode
+    delimiters: uint,
+    nesting: Nesting,
+    // brace depth of the unsafe region being highlighted
+    unsafe_depth: Option<uint>,
+    // set after a token which may end an item or a statement
+    at_item: bool,
+    // set after an `unsafe` which is followed by a body, with its parenthesis
+    // and bracket depth
+    unsafe_pending: Option<uint>,
+    // set inside a code block of a doc comment
+    doc_code: Option<DocCode>,
+    // lifetimes seen so far, in order of appearance
+    lifetimes: Vec<~str>,
+    semantic: Semantic,
+    variables: Variables,
+}
+
+// The opening fence of a code block in doc comments: the offsets of the doc
+// comment holding it, and of the fence itself.
+#[deriving(Clone, Eq)]
+struct DocCode {
+    comment: uint,
+    fence: uint,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            regions: Vec::new(),
+            attribute: None,
+            attribute_depth: 0,
+            is_macro: false,
+            is_macro_nonterminal: false,
+            is_label: false,
+            macro_rules: Vec::new(),
+            format_calls: Vec::new(),
+            delimiters: 0,
+            nesting: Nesting::new(),
+            unsafe_depth: None,
+            at_item: true,
+            unsafe_pending: None,
+            doc_code: None,
+            lifetimes: Vec::new(),
+            semantic: Semantic::new(),
+            variables: Variables::new(),
+        }
+    }
+}
+
+/// A point between two tokens where highlighting can be resumed.
+#[deriving(Clone)]
+pub struct Checkpoint {
+    offset: uint,
+    pos: Pos,
+    state: State,
+}
+
+impl Checkpoint {
+    pub fn offset(&self) -> uint {
+        self.offset
+    }
+
+    pub fn pos(&self) -> Pos {
+        self.pos.clone()
+    }
+}
+
+/// A lazy iterator over the highlighted parts of a source.
+///
+/// Tokens are lexed on demand, so only the parts of the token being
+/// classified are ever buffered. The texts of the parts, in order, make up the
+/// source exactly.
+pub struct Highlighter<'a> {
+    src: &'a str,
+    lexer: Lexer<'a>,
+    map: LineMap<'a>,
+    queue: RingBuf<Part>,
+    done: bool,
+    options: Options,
+    // offsets of the unmatched delimiters, in rainbow mode
+    unmatched: HashSet<uint>,
+    // lines of the code block following the fence at `doc_fence`, with
+    // their offsets; they are highlighted from the source when the fence is
+    // reached, and aren't part of the state
+    doc_fence: Option<uint>,
+    doc_lines: RingBuf<(uint, Vec<CodePart>)>,
+
+    last: uint,
+    state: State,
+}
+
+impl<'a> Highlighter<'a> {
+    pub fn new(src: &'a str) -> Highlighter<'a> {
+        Highlighter::with_options(src, Options::new())
+    }
+
+    pub fn with_options(src: &'a str, options: Options) -> Highlighter<'a> {
+        let unmatched = if options.rainbow > 0 {
+            unmatched_delimiters(src)
+        } else {
+            HashSet::new()
+        };
+
+        Highlighter {
+            src: src,
+            lexer: Lexer::new(src),
+            map: LineMap::new(src),
+            queue: RingBuf::new(),
+            done: false,
+            options: options,
+            unmatched: unmatched,
+            doc_fence: None,
+            doc_lines: RingBuf::new(),
+
+            last: 0,
+            state: State::new(),
+        }
+    }
+
+    /// Resumes highlighting `src` from a checkpoint taken on a source which
+    /// is identical to `src` up to the checkpoint, with the same options.
+    pub fn resume(src: &'a str, options: Options, checkpoint: &Checkpoint) -> Highlighter<'a> {
+        let unmatched = if options.rainbow > 0 {
+            unmatched_delimiters(src)
+        } else {
+            HashSet::new()
+        };
+
+        Highlighter {
+            src: src,
+            lexer: Lexer::resume(src, checkpoint.offset),
+            map: LineMap::resume(src, checkpoint.offset, checkpoint.pos.clone()),
+            queue: RingBuf::new(),
+            done: false,
+            options: options,
+            unmatched: unmatched,
+            doc_fence: None,
+            doc_lines: RingBuf::new(),
+
+            last: checkpoint.offset,
+            state: checkpoint.state.clone(),
+        }
+    }
+
+    /// Returns a checkpoint for the current position, if all parts produced
+    /// so far have been consumed.
+    pub fn checkpoint(&mut self) -> Option<Checkpoint> {
+        if !self.queue.is_empty() || self.done {
+            return None;
+        }
+
+        let offset = self.last;
+        Some(Checkpoint {
+            offset: offset,
+            pos: self.map.pos(offset),
+            state: self.state.clone(),
+        })
+    }
+
+    fn text(&mut self, ty: &str, lo: uint, hi: uint) {
+        let span = self.map.span(lo, hi);
+        let text = self.src.slice(lo, hi).into_owned();
+        self.queue.push_back(Text(ty.to_owned(), text, span));
+    }
+
+    fn start(&mut self, ty: &str, offset: uint) {
+        let span = self.map.span(offset, offset);
+        self.queue.push_back(Start(ty.to_owned(), span));
+    }
+
+    fn end(&mut self, ty: &str, offset: uint) {
+        let span = self.map.span(offset, offset);
+        self.queue.push_back(End(ty.to_owned(), span));
+    }
+
+    // Starts a region which spans several tokens, and which is ended at the
+    // end of the source if it is still open there.
+    fn open(&mut self, ty: &'static str, offset: uint) {
+        self.state.regions.push(ty);
+        self.start(ty, offset);
+    }
+
+    fn close(&mut self, ty: &'static str, offset: uint) {
+        match self.state.regions.iter().rposition(|r| *r == ty) {
+            Some(i) => {
+                self.state.regions.remove(i);
+            }
+            None => {}
+        }
+        self.end(ty, offset);
+    }
+
+    // Emits the token `lo..hi` split into segments, inside a region of class
+    // `ty`. Tokens which aren't split are emitted as a single part.
+    fn segments(&mut self, ty: &str, lo: uint, hi: uint, segments: Vec<Segment>) {
+        if segments.len() <= 1 {
+            self.text(ty, lo, hi);
+            return;
+        }
+
+        self.start(ty, lo);
+        self.emit(lo, segments);
+        self.end(ty, hi);
+    }
+
+    // Emits segments whose offsets are relative to `lo`.
+    fn emit(&mut self, lo: uint, segments: Vec<Segment>) {
+        for segment in segments.move_iter() {
+            match segment {
+                Open(class, offset) => self.start(class, lo + offset),
+                Close(class, offset) => self.end(class, lo + offset),
+                Piece(class, a, b) => self.text(class, lo + a, lo + b),
+            }
+        }
+    }
+
+    // Emits a doc comment line by line, the comment syntax between the lines
+    // being plain text.
+    fn doc_comment(&mut self, ty: &str, tok: &Token) {
+        let lines = doc::lines(self.src.slice(tok.lo, tok.hi));
+
+        self.start(ty, tok.lo);
+        let mut plain = tok.lo;
+        for (i, &(lo, hi)) in lines.iter().enumerate() {
+            if tok.lo + lo > plain {
+                self.text("", plain, tok.lo + lo);
+            }
+            self.doc_line(tok, tok.lo + lo, tok.lo + hi);
+            plain = tok.lo + hi;
+        }
+        if tok.hi > plain {
+            self.text("", plain, tok.hi);
+        }
+        self.end(ty, tok.hi);
+    }
+
+    // Emits the line `lo..hi` of the doc comment `tok`.
+    fn doc_line(&mut self, tok: &Token, lo: uint, hi: uint) {
+        let src = self.src;
+        let line = src.slice(lo, hi);
+
+        if self.state.doc_code.is_some() {
+            if doc::fence(line).is_some() {
+                self.state.doc_code = None;
+                self.text("doc-fence", lo, hi);
+                return;
+            }
+
+            // The code block is highlighted again when highlighting is
+            // resumed inside it.
+            let code = self.state.doc_code.clone().unwrap();
+            if self.doc_fence != Some(code.fence) {
+                self.code_block(&code);
+            }
+            while self.doc_lines.front().map_or(false, |&(offset, _)| offset < lo) {
+                self.doc_lines.pop_front();
+            }
+            if lo == hi {
+                return;
+            }
+
+            let found = self.doc_lines.front().map_or(false, |&(offset, _)| offset == lo);
+            let parts = if found {
+                self.doc_lines.pop_front().map(|(_, parts)| parts)
+            } else {
+                None
+            };
+            match parts {
+                Some(parts) => {
+                    self.start("doc-code-block", lo);
+                    for part in parts.move_iter() {
+                        match part {
+                            CodeStart(ty, offset) => self.start(ty.as_slice(), lo + offset),
+                            CodeEnd(ty, offset) => self.end(ty.as_slice(), lo + offset),
+                            CodeText(ty, a, b) => self.text(ty.as_slice(), lo + a, lo + b),
+                        }
+                    }
+                    self.end("doc-code-block", hi);
+                }
+                None => self.text("doc-code-block", lo, hi),
+            }
+            return;
+        }
+
+        match doc::fence(line) {
+            Some(_) => {
+                self.text("doc-fence", lo, hi);
+                let code = DocCode {
+                    comment: tok.lo,
+                    fence: lo,
+                };
+                self.code_block(&code);
+                self.state.doc_code = Some(code);
+            }
+            None => {
+                let segments = doc::markdown(line, self.options.markers.as_slice());
+                self.emit(lo, segments);
+            }
+        }
+    }
+
+    // Emits the frontmatter of a cargo script: its fences, and the TOML
+    // between them.
+    fn frontmatter(&mut self, tok: &Token) {
+        let text = self.src.slice(tok.lo, tok.hi);
+        let open = text.find('\n').unwrap_or(text.len());
+        let close = text.rfind('\n').map_or(text.len(), |i| i + 1);
+
+        self.start("frontmatter", tok.lo);
+        self.text("frontmatter-fence", tok.lo, tok.lo + open);
+        if close > open {
+            self.emit(tok.lo + open, toml::highlight(text.slice(open, close)));
+            self.text("frontmatter-fence", tok.lo + close, tok.hi);
+        }
+        self.end("frontmatter", tok.hi);
+    }
+
+    // Highlights the lines of the code block following a fence, from the
+    // current source. Code blocks in other languages are left plain.
+    fn code_block(&mut self, code: &DocCode) {
+        let src = self.src;
+        let mut lexer = Lexer::resume(src, code.comment);
+        let tok = lexer.next().unwrap();
+        let lines = doc::lines(src.slice(tok.lo, tok.hi));
+        let i = lines.iter().position(|&(lo, _)| tok.lo + lo == code.fence).unwrap();
+        let &(_, hi) = lines.get(i);
+
+        self.doc_fence = Some(code.fence);
+        self.doc_lines = RingBuf::new();
+        if !doc::is_rust(doc::fence(src.slice(code.fence, tok.lo + hi)).unwrap()) {
+            return;
+        }
+
+        let lines = self.code_lines(&tok, lines.slice_from(i + 1), lexer);
+        let texts: Vec<&str> = lines.iter().map(|&(_, line)| line).collect();
+        let parts = doc::code(texts.as_slice(), &self.options);
+        for (&(offset, _), parts) in lines.iter().zip(parts.move_iter()) {
+            self.doc_lines.push_back((offset, parts));
+        }
+    }
+
+    // Gathers the lines of a code block which starts in the doc comment `tok`,
+    // before its lines `rest`, with their offsets. The block goes on in the
+    // doc comments of the same kind which `lexer` finds next, until the
+    // closing fence.
+    fn code_lines(&self, tok: &Token, rest: &[(uint, uint)], mut lexer: Lexer<'a>) -> Vec<(uint, &'a str)> {
+        let src = self.src;
+        let mut lines = Vec::new();
+
+        for &(lo, hi) in rest.iter() {
+            let line = src.slice(tok.lo + lo, tok.lo + hi);
+            if doc::fence(line).is_some() {
+                return lines;
+            }
+            lines.push((tok.lo + lo, line));
+        }
+
+        loop {
+            match lexer.next() {
+                Some(Token { kind: Whitespace, .. }) => {}
+                Some(ref next) if next.kind == tok.kind && !next.error => {
+                    let text = src.slice(next.lo, next.hi);
+                    for &(lo, hi) in doc::lines(text).iter() {
+                        let line = text.slice(lo, hi);
+                        if doc::fence(line).is_some() {
+                            return lines;
+                        }
+                        lines.push((next.lo + lo, line));
+                    }
+                }
+                _ => return lines,
+            }
+        }
+    }
+
+    // Classifies the next significant token, after emitting the whitespace and
+    // comments before it.
+    fn step(&mut self) {
+        let src = self.src;
+        let next;
+        loop {
+            match self.lexer.next() {
+                Some(ref tok) if tok.is_trivia() => {
+                    let ty = match tok.kind {
+                        LineComment(..) => "comment",
+                        BlockComment(..) => "block-comment",
+                        _ => {
+                            self.text("whitespace", tok.lo, tok.hi);
+                            continue;
+                        }
+                    };
+                    let segments = comment::split(src.slice(tok.lo, tok.hi), self.options.markers.as_slice());
+                    self.segments(ty, tok.lo, tok.hi, segments);
+                }
+                tok => {
+                    next = tok;
+                    break;
+                }
+            }
+        }
+        let next = match next {
+            Some(tok) => tok,
+            None => {
+                // Regions left open by a truncated source, such as a pasted
+                // `#[derive(Debug)` snippet, are ended at its end, innermost
+                // first.
+                let end = src.len();
+                loop {
+                    match self.state.regions.pop() {
+                        Some(ty) => self.end(ty, end),
+                        None => break,
+                    }
+                }
+
+                self.last = end;
+                self.done = true;
+                return;
+            }
+        };
+        self.last = next.hi;
+        let snip = src.slice(next.lo, next.hi);
+
+        // Code blocks of doc comments end with the doc comment.
+        if !next.is_comment() || next.error {
+            self.state.doc_code = None;
+        }
+
+        let rules = if next.error || next.is_comment() || self.state.macro_rules.is_empty() {
+            None
+        } else {
+            let peek = self.peek_text();
+            Some(self.state.macro_rules.mut_last().unwrap().push(snip, peek))
+        };
+
+        match rules {
+            Some(ref step) => match step.start {
+                Some(ty) => self.open(ty, next.lo),
+                None => {}
+            },
+            None => {}
+        }
+
+        let is_str = match next.kind {
+            Str | RawStr => true,
+            _ => false,
+        };
+        let format = match self.state.format_calls.mut_last() {
+            _ if next.error || next.is_comment() => (false, false),
+            Some(call) => call.push(snip, is_str),
+            None => (false, false),
+        };
+        let (is_format_string, format_done) = format;
+        if format_done {
+            self.state.format_calls.pop();
+        }
+
+        // Unsafe blocks, and the bodies of unsafe functions and impls, are in
+        // an `unsafe-block` region. Nested ones are part of the outer one.
+        let mut unsafe_end = false;
+        if next.kind == Punct && !next.error {
+            let nesting = self.state.nesting.clone();
+            match snip {
+                "{" => {
+                    if self.state.unsafe_pending == Some(nesting.parens) {
+                        if self.state.unsafe_depth.is_none() {
+                            self.state.unsafe_depth = Some(nesting.braces);
+                            self.open("unsafe-block", next.lo);
+                        }
+                        self.state.unsafe_pending = None;
+                    }
+                }
+                "}" => {
+                    let braces = if nesting.braces > 0 { nesting.braces - 1 } else { 0 };
+                    if self.state.unsafe_depth == Some(braces) {
+                        self.state.unsafe_depth = None;
+                        unsafe_end = true;
+                    }
+                }
+                ")" | "]" => {
+                    let parens = if nesting.parens > 0 { nesting.parens - 1 } else { 0 };
+                    if self.state.unsafe_pending.map_or(false, |depth| depth > parens) {
+                        self.state.unsafe_pending = None;
+                    }
+                }
+                // The `;` of `[u8; 4]` in the parameters doesn't end the
+                // declaration.
+                ";" if self.state.unsafe_pending == Some(nesting.parens) => {
+                    self.state.unsafe_pending = None;
+                }
+                _ => {}
+            }
+        }
+
+        let ty = match rules {
+            Some(ref step) if step.class.is_some() => step.class,
+            _ if is_format_string => {
+                self.segments("string", next.lo, next.hi, literal::string(snip, true));
+                None
+            }
+            _ => self.classify(&next),
+        };
+        match ty {
+            Some(ty) => self.text(ty, next.lo, next.hi),
+            None => {}
+        }
+        if unsafe_end {
+            self.close("unsafe-block", next.hi);
+        }
+
+        match rules {
+            Some(ref step) => {
+                match step.end {
+                    Some(ty) => self.close(ty, next.hi),
+                    None => {}
+                }
+                if step.done {
+                    self.state.macro_rules.pop();
+                }
+            }
+            None => {}
+        }
+
+        if !next.is_comment() && !next.error {
+            self.state.at_item = ITEM_PREV.iter().any(|p| *p == snip);
+        }
+        if !next.is_comment() {
+            if self.options.semantic {
+                self.state.semantic.push(snip, &self.state.nesting);
+            }
+            if self.options.variables > 0 {
+                let peek = self.peek_text();
+                self.state.variables.push(snip, peek, &self.state.nesting);
+            }
+            self.state.nesting.push(snip);
+        }
+    }
+
+    // Returns the text of the next significant token, or an empty string at
+    // the end of the source.
+    fn peek_text(&self) -> &'a str {
+        match next_significant(&mut self.lexer.clone()) {
+            Some(ref tok) => self.src.slice(tok.lo, tok.hi),
+            None => "",
+        }
+    }
+
+    // Emits a delimiter in rainbow mode, with the class of its nesting depth.
+    // Openers and closers of a matched pair share the same depth.
+    fn delimiter(&mut self, lo: uint, hi: uint) {
+        if self.unmatched.contains(&lo) {
+            self.text("delim-unmatched", lo, hi);
+            return;
+        }
+
+        let depth = match self.src.char_at(lo) {
+            '(' | '[' | '{' => {
+                self.state.delimiters += 1;
+                self.state.delimiters - 1
+            }
+            _ => {
+                self.state.delimiters -= 1;
+                self.state.delimiters
+            }
+        };
+        let ty = format!("delim-{}", depth % self.options.rainbow);
+        self.text(ty.as_slice(), lo, hi);
+    }
+
+    // Emits a lifetime in per-lifetime mode, with the class of its index.
+    fn lifetime(&mut self, lo: uint, hi: uint) {
+        let name = self.src.slice(lo, hi);
+        let ty = match name {
+            "'static" => "lifetime-static".to_owned(),
+            "'_" => "lifetime-anon".to_owned(),
+            _ => {
+                let index = match self.state.lifetimes.iter().position(|l| l.as_slice() == name) {
+                    Some(index) => index,
+                    None => {
+                        self.state.lifetimes.push(name.to_owned());
+                        self.state.lifetimes.len() - 1
+                    }
+                };
+                format!("lifetime-{}", index % self.options.lifetimes)
+            }
+        };
+        self.text(ty.as_slice(), lo, hi);
+    }
+
+    // Returns the class of a significant token, or `None` if the token has
+    // been handled already.
+    fn classify(&mut self, next: &Token) -> Option<&'static str> {
+        let src = self.src;
+        let mut lookahead = self.lexer.clone();
+        let peek = next_significant(&mut lookahead);
+        let peek_text = match peek {
+            Some(ref tok) => src.slice(tok.lo, tok.hi),
+            None => "",
+        };
+        let snip = src.slice(next.lo, next.hi);
+
+        let is_label = self.state.is_label;
+        self.state.is_label = false;
+
+        let is_inner_attribute = snip == "#" && peek_text == "!" &&
+            match next_significant(&mut lookahead.clone()) {
+                Some(ref tok) => src.slice(tok.lo, tok.hi) == "[",
+                None => false,
+            };
+
+        let ty = match next.kind {
+            // Malformed tokens (unterminated literals or comments, stray
+            // characters) are kept as-is so that no input is lost.
+            _ if next.error => "error",
+
+            Punct => match snip {
+                // If this '&' token is directly adjacent to another token,
+                // assume that it's the address-of operator instead of the
+                // and-operator. This allows us to give all pointers their own
+                // class (~ and @ are below).
+                "&" if peek.as_ref().map_or(false, |tok| tok.lo == next.hi) => "kw-2",
+                "@" | "~" => "kw-2",
+
+                // consider this as part of a macro invocation if there was a
+                // leading identifier
+                "!" if self.state.is_macro => { self.state.is_macro = false; "macro" }
+
+                ".." | "..." | "..=" => "range-op",
+                "?" => "try-op",
+
+                // operators
+                "=" | "<" | "<=" | "==" | "!=" | ">=" | ">" | "&&" | "||" |
+                    "!" | "+" | "-" | "*" | "/" | "%" | "^" | "&" | "|" |
+                    "<<" | ">>" | "->" | "+=" | "-=" | "*=" | "/=" | "%=" |
+                    "^=" | "&=" | "|=" | "<<=" | ">>=" | "=>" => "op",
+
+                "$" => {
+                    match peek {
+                        Some(Token { kind: Ident, .. }) => {
+                            self.state.is_macro_nonterminal = true;
+                            "macro-nonterminal"
+                        }
+                        _ => "",
+                    }
+                }
+
+                // This is the start of an attribute (`#[...]`, or `#![...]`
+                // for inner ones). Its tokens are highlighted as usual, inside
+                // an attribute region which ends with the ']' matching the
+                // opening '['.
+                "#" if self.state.attribute.is_none() &&
+                    (peek_text == "[" || is_inner_attribute) => {
+                    let ty = if is_inner_attribute {
+                        "inner-attribute"
+                    } else {
+                        "attribute"
+                    };
+                    self.state.attribute = Some(ty);
+                    self.state.attribute_depth = 0;
+                    self.open(ty, next.lo);
+                    ""
+                }
+                "[" if self.state.attribute.is_some() => {
+                    self.state.attribute_depth += 1;
+                    ""
+                }
+                "]" if self.state.attribute.is_some() => {
+                    self.state.attribute_depth -= 1;
+                    if self.state.attribute_depth == 0 {
+                        let ty = self.state.attribute.take_unwrap();
+                        self.text("", next.lo, next.hi);
+                        self.close(ty, next.hi);
+                        return None
+                    }
+                    ""
+                }
+
+                "(" | "[" | "{" | ")" | "]" | "}" if self.options.rainbow > 0 => {
+                    self.delimiter(next.lo, next.hi);
+                    return None
+                }
+
+                // miscellaneous, no highlighting
+                _ => "",
+            },
+
+            // text literals, with their escape sequences
+            Char | Str | RawStr | Byte | ByteStr | RawByteStr | CStr | RawCStr => {
+                let ty = match next.kind {
+                    Byte => "byte",
+                    ByteStr | RawByteStr => "byte-string",
+                    CStr | RawCStr => "c-string",
+                    _ => "string",
+                };
+                self.segments(ty, next.lo, next.hi, literal::string(snip, false));
+                return None
+            }
+
+            // number literals, split into prefix, digits, exponent and suffix
+            Number => {
+                self.segments("number", next.lo, next.hi, literal::number(snip));
+                return None
+            }
+
+            // keywords are also included in the identifier set
+            Ident => {
+                match snip {
+                    "ref" | "mut" => "kw-2",
+
+                    "self" => "self",
+                    "false" | "true" => "boolval",
+
+                    _ if self.options.names.find(snip, self.options.edition).is_some() => {
+                        let class = self.options.names.find(snip, self.options.edition).unwrap().to_owned();
+                        self.text(class.as_slice(), next.lo, next.hi);
+                        return None
+                    }
+
+                    "_" => "",
+
+                    "macro_rules" if peek_text == "!" => {
+                        self.state.macro_rules.push(MacroRules::new());
+                        self.state.is_macro = true;
+                        "macro"
+                    }
+
+                    // `unsafe fn(...)` pointer types, which are never at the
+                    // start of an item, and `#[unsafe(...)]` attributes have
+                    // no body.
+                    "unsafe" => {
+                        let has_body = match peek_text {
+                            "{" => true,
+                            "fn" | "impl" | "extern" => self.state.at_item,
+                            _ => false,
+                        };
+                        self.state.unsafe_pending = if has_body {
+                            Some(self.state.nesting.parens)
+                        } else {
+                            None
+                        };
+                        "kw"
+                    }
+
+                    "break" | "continue" => {
+                        self.state.is_label = true;
+                        "kw"
+                    }
+
+                    _ if is_keyword(snip, self.options.edition) => "kw",
+                    _ => {
+                        if self.state.is_macro_nonterminal {
+                            self.state.is_macro_nonterminal = false;
+                            "macro-nonterminal"
+                        } else if peek_text == "!" {
+                            self.state.is_macro = true;
+                            match FormatCall::new(snip) {
+                                Some(call) => self.state.format_calls.push(call),
+                                None => {}
+                            }
+                            "macro"
+                        } else {
+                            let variable = if self.options.variables > 0 {
+                                self.state.variables.classify(snip, peek_text)
+                            } else {
+                                None
+                            };
+
+                            match variable {
+                                Some(index) => {
+                                    let ty = format!("var-{}", index % self.options.variables);
+                                    self.text(ty.as_slice(), next.lo, next.hi);
+                                    return None
+                                }
+                                None if self.options.semantic => {
+                                    let peek2 = match next_significant(&mut lookahead.clone()) {
+                                        Some(ref tok) => src.slice(tok.lo, tok.hi),
+                                        None => "",
+                                    };
+                                    self.state.semantic.classify(snip, peek_text, peek2).unwrap_or("ident")
+                                }
+                                None => "ident",
+                            }
+                        }
+                    }
+                }
+            }
+
+            RawIdent => "raw-ident",
+
+            // The newline ending the shebang is kept out of it.
+            Shebang => {
+                let hi = if snip.ends_with("\n") { next.hi - 1 } else { next.hi };
+                self.text("shebang", next.lo, hi);
+                if hi < next.hi {
+                    self.text("whitespace", hi, next.hi);
+                }
+                return None
+            }
+            Frontmatter => {
+                self.frontmatter(next);
+                return None
+            }
+
+            // A label is either used by `break`/`continue`, or declared in
+            // front of a loop or a block (`'outer: loop`), which isn't to be
+            // confused with a lifetime bound (`'a: 'b`).
+            Lifetime if is_label => "label",
+            Lifetime if peek_text == ":" => {
+                match next_significant(&mut lookahead) {
+                    Some(ref tok) => match src.slice(tok.lo, tok.hi) {
+                        "loop" | "while" | "for" | "{" => "label",
+                        _ if self.options.lifetimes > 0 => {
+                            self.lifetime(next.lo, next.hi);
+                            return None
+                        }
+                        _ => "lifetime",
+                    },
+                    None => "lifetime",
+                }
+            }
+            Lifetime if self.options.lifetimes > 0 => {
+                self.lifetime(next.lo, next.hi);
+                return None
+            }
+            Lifetime => "lifetime",
+            LineComment(style) | BlockComment(style) => {
+                let ty = if style == InnerDoc {
+                    "inner-doccomment"
+                } else {
+                    "doccomment"
+                };
+                self.doc_comment(ty, next);
+                return None
+            }
+            Whitespace | Unknown => "",
+        };
+
+        Some(ty)
+    }
+}
+
+impl<'a> Iterator<Part> for Highlighter<'a> {
+    fn next(&mut self) -> Option<Part> {
+        while self.queue.is_empty() && !self.done {
+            self.step();
+        }
+
+        self.queue.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Highlighter, Options, check};
+    use super::lexer::{Lexer, Token, Whitespace};
+
+    static SOURCES: &'static [&'static str] = &[
+        "/* a /* b */ c */ x",
+        "/* a /* b */",
+        "r##\"a \"# b\"## z",
+        "r#\"a",
+        "\"abc",
+        "'\\n",
+        "' \nx",
+        "1..2",
+        "1.foo()",
+        "r#type",
+        "x<-1",
+        "'r#try: loop {}",
+        "#!/usr/bin/env cargo\n---\n[dependencies]\n---\nfn main() {}\n",
+        "---\nx\n",
+        "#[derive(Debug)",
+        "unsafe { macro_rules! m { () => {",
+        "/// ```\n/// let s = \"a\n/// ```\nfn f<'a, T: Fn(&'a str)>(x: &'a T) { println!(\"{}\", x); }\n",
+    ];
+
+    // Returns the texts of the tokens of `src` which aren't whitespace, and
+    // whether they have the error flag.
+    fn tokens(src: &str) -> Vec<(~str, bool)> {
+        Lexer::new(src).filter(|tok: &Token| tok.kind != Whitespace)
+            .map(|tok| (src.slice(tok.lo, tok.hi).to_owned(), tok.error)).collect()
+    }
+
+    fn ok(texts: &[&str]) -> Vec<(~str, bool)> {
+        texts.iter().map(|text| (text.to_owned(), false)).collect()
+    }
+
+    #[test]
+    fn lossless() {
+        let mut options = Options::new();
+        options.semantic = true;
+        options.rainbow = 6;
+        options.lifetimes = 6;
+        options.variables = 8;
+
+        for src in SOURCES.iter() {
+            assert_eq!(check(src.as_bytes(), Highlighter::new(*src)), None);
+            assert_eq!(check(src.as_bytes(), Highlighter::with_options(*src, options.clone())), None);
+        }
+    }
+
+    #[test]
+    fn tokens_and_errors() {
+        assert_eq!(tokens("/* a /* b */ c */ x"), ok(&["/* a /* b */ c */", "x"]));
+        assert_eq!(tokens("/* a /* b */"), vec!(("/* a /* b */".to_owned(), true)));
+        assert_eq!(tokens("r##\"a \"# b\"## z"), ok(&["r##\"a \"# b\"##", "z"]));
+        assert_eq!(tokens("r#\"a"), vec!(("r#\"a".to_owned(), true)));
+        assert_eq!(tokens("\"abc"), vec!(("\"abc".to_owned(), true)));
+        assert_eq!(tokens("'\\n"), vec!(("'\\n".to_owned(), true)));
+        assert_eq!(tokens("' \nx"), vec!(("' ".to_owned(), true), ("x".to_owned(), false)));
+        assert_eq!(tokens("1..2"), ok(&["1", "..", "2"]));
+        assert_eq!(tokens("1.foo()"), ok(&["1", ".", "foo", "(", ")"]));
+        assert_eq!(tokens("r#type"), ok(&["r#type"]));
+        assert_eq!(tokens("x<-1"), ok(&["x", "<", "-", "1"]));
+        assert_eq!(tokens("'r#try: loop {}"), ok(&["'r#try", ":", "loop", "{", "}"]));
+        assert_eq!(tokens("#!/usr/bin/env cargo\n---\n[dependencies]\n---\nfn main() {}\n"),
+                   ok(&["#!/usr/bin/env cargo\n", "---\n[dependencies]\n---", "fn", "main", "(", ")", "{", "}"]));
+        assert_eq!(tokens("---\nx\n"), vec!(("---\nx\n".to_owned(), true)));
+    }
+}
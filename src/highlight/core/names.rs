@@ -0,0 +1,110 @@
+// Classes of well-known names, such as the types and values of the prelude.
+//
+// The table can be extended, with lines of `class = Name, Name` in a file, or
+// with `names.class=Name,Name` variables.
+
+use collections::HashMap;
+
+use super::Edition;
+
+static PRELUDE_TYPES: &'static [&'static str] = &[
+    "AsMut", "AsRef", "AsyncFn", "AsyncFnMut", "AsyncFnOnce", "Box", "Clone",
+    "Copy", "Default",
+    "DoubleEndedIterator", "Drop", "Eq", "ExactSizeIterator", "Extend", "Fn",
+    "FnMut", "FnOnce", "From", "Into", "IntoIterator", "Iterator", "Option",
+    "Ord", "PartialEq", "PartialOrd", "Result", "Send", "Sized", "String",
+    "Sync", "ToOwned", "ToString", "Unpin", "Vec",
+];
+
+static PRELUDE_VALUES: &'static [&'static str] = &[
+    "Some", "None", "Ok", "Err", "align_of", "align_of_val", "drop", "size_of",
+    "size_of_val",
+];
+
+// Types added to the prelude by an edition, and the year of that edition.
+static EDITION_PRELUDE_TYPES: &'static [(&'static str, uint)] = &[
+    ("FromIterator", 2021), ("TryFrom", 2021), ("TryInto", 2021),
+    ("Future", 2024), ("IntoFuture", 2024),
+];
+
+// Returns the class of `name` in the prelude of `edition`.
+fn prelude(name: &str, edition: Edition) -> Option<&'static str> {
+    if PRELUDE_TYPES.iter().any(|n| *n == name) ||
+        EDITION_PRELUDE_TYPES.iter().any(|&(n, year)| n == name && edition.year() >= year) {
+        Some("prelude-ty")
+    } else if PRELUDE_VALUES.iter().any(|n| *n == name) {
+        Some("prelude-val")
+    } else {
+        None
+    }
+}
+
+/// The registered names, which come on top of the prelude.
+#[deriving(Clone)]
+pub struct Names {
+    classes: HashMap<~str, ~str>,
+}
+
+impl Names {
+    pub fn new() -> Names {
+        Names {
+            classes: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, name: &str, class: &str) {
+        self.classes.insert(name.to_owned(), class.to_owned());
+    }
+
+    /// Returns the class of `name`, looking it up in the prelude of `edition`
+    /// if it hasn't been registered.
+    pub fn find<'a>(&'a self, name: &str, edition: Edition) -> Option<&'a str> {
+        match self.classes.find_equiv::<&str>(&name) {
+            Some(class) => Some(class.as_slice()),
+            None => prelude(name, edition),
+        }
+    }
+
+    fn insert_all(&mut self, class: &str, names: &str) -> Result<(), ~str> {
+        let class = class.trim();
+        if class.len() == 0 {
+            return Err(format!("Missing class for names: {}", names));
+        }
+
+        for name in names.split(',').map(|name| name.trim()).filter(|name| name.len() > 0) {
+            self.insert(name, class);
+        }
+
+        Ok(())
+    }
+
+    /// Reads lines of `class = Name, Name`. Empty lines and lines starting
+    /// with `#` are ignored.
+    pub fn load(&mut self, text: &str) -> Result<(), ~str> {
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.len() == 0 || line.starts_with("#") {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.splitn('=', 1).collect();
+            if parts.len() != 2 {
+                return Err(format!("Bad names line {}: {}", i + 1, line));
+            }
+            try!(self.insert_all(*parts.get(0), *parts.get(1)));
+        }
+
+        Ok(())
+    }
+
+    /// Reads `names.class=Name,Name` variables, ignoring the other ones.
+    pub fn configure(&mut self, vars: &HashMap<~str, ~str>) -> Result<(), ~str> {
+        for (key, value) in vars.iter() {
+            if key.starts_with("names.") {
+                try!(self.insert_all(key.slice_from("names.".len()), value.as_slice()));
+            }
+        }
+
+        Ok(())
+    }
+}
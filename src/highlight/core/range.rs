@@ -0,0 +1,173 @@
+// Highlighting of a range of lines.
+//
+// The whole source is highlighted, so that tokens which start before the range
+// (a block comment or a raw string) are still recognized, and the parts are
+// then cut down to the range.
+
+use collections::{Deque, RingBuf};
+
+use super::{Part, Start, End, Text, Pos, Span};
+
+/// Keeps the parts of the lines `first` to `last` (both included, starting at
+/// 1) of a highlighted source, without the newline ending the last one.
+///
+/// Texts which straddle the bounds of the range are cut. The regions open at
+/// the start of the range are started there, and those still open at its end
+/// are ended there, so that starts and ends stay balanced. Spans keep their
+/// positions in the whole source.
+pub struct LineRange<T> {
+    parts: T,
+    first: uint,
+    last: uint,
+    // classes of the regions open at the current part, innermost last
+    open: Vec<~str>,
+    entered: bool,
+    done: bool,
+    // end of the last text kept
+    end: Option<Span>,
+    queue: RingBuf<Part>,
+}
+
+fn point(offset: uint, pos: Pos) -> Span {
+    Span {
+        lo: offset,
+        hi: offset,
+        start: pos.clone(),
+        end: pos,
+    }
+}
+
+// Returns the start of the part of `text` which is in the lines `first` to
+// `last`, and its end if the text goes on after the range. Offsets are
+// relative to the text, which starts at `pos`, on line `last` at most.
+fn cut(text: &str, pos: &Pos, first: uint, last: uint) -> (Option<(uint, Pos)>, Option<(uint, Pos)>) {
+    let mut lo = if pos.line >= first { Some((0, pos.clone())) } else { None };
+    let mut line = pos.line;
+    let mut col = pos.col;
+
+    for (i, c) in text.char_indices() {
+        if c != '\n' {
+            col += 1;
+            continue;
+        }
+
+        if line == last {
+            return (lo, Some((i, Pos { line: line, col: col })));
+        }
+        line += 1;
+        col = 0;
+        if line == first {
+            lo = Some((i + 1, Pos { line: line, col: 0 }));
+        }
+    }
+
+    (lo, None)
+}
+
+impl<T: Iterator<Part>> LineRange<T> {
+    pub fn new(parts: T, first: uint, last: uint) -> LineRange<T> {
+        LineRange {
+            parts: parts,
+            first: first,
+            last: last,
+            open: Vec::new(),
+            entered: false,
+            done: false,
+            end: None,
+            queue: RingBuf::new(),
+        }
+    }
+
+    // Starts the regions open at the start of the range.
+    fn enter(&mut self, at: &Span) {
+        self.entered = true;
+        for ty in self.open.iter() {
+            self.queue.push_back(Start(ty.clone(), at.clone()));
+        }
+    }
+
+    // Ends the regions open at the end of the range.
+    fn finish(&mut self) {
+        self.done = true;
+        if !self.entered {
+            return;
+        }
+
+        let at = self.end.clone().unwrap();
+        for ty in self.open.iter().rev() {
+            self.queue.push_back(End(ty.clone(), at.clone()));
+        }
+    }
+
+    fn push(&mut self, part: Part) {
+        match part {
+            Start(ty, span) => {
+                if span.start.line > self.last {
+                    self.finish();
+                    return;
+                }
+                if self.entered {
+                    self.queue.push_back(Start(ty.clone(), span));
+                }
+                self.open.push(ty);
+            }
+            End(ty, span) => {
+                if span.start.line > self.last {
+                    self.finish();
+                    return;
+                }
+                if self.entered {
+                    self.queue.push_back(End(ty, span));
+                }
+                self.open.pop();
+            }
+            Text(ty, text, span) => {
+                if span.start.line > self.last {
+                    self.finish();
+                    return;
+                }
+
+                let (lo, hi) = cut(text.as_slice(), &span.start, self.first, self.last);
+                let (lo, lo_pos) = match lo {
+                    Some(lo) => lo,
+                    None => return,
+                };
+                let (hi, hi_pos) = match hi {
+                    Some(hi) => hi,
+                    None => (text.len(), span.end.clone()),
+                };
+
+                if !self.entered {
+                    self.enter(&point(span.lo + lo, lo_pos.clone()));
+                }
+                if hi > lo {
+                    let cut = Span {
+                        lo: span.lo + lo,
+                        hi: span.lo + hi,
+                        start: lo_pos,
+                        end: hi_pos.clone(),
+                    };
+                    self.queue.push_back(Text(ty, text.slice(lo, hi).to_owned(), cut));
+                }
+                self.end = Some(point(span.lo + hi, hi_pos));
+
+                if hi < text.len() {
+                    self.finish();
+                }
+            }
+        }
+    }
+}
+
+impl<T: Iterator<Part>> Iterator<Part> for LineRange<T> {
+    fn next(&mut self) -> Option<Part> {
+        while self.queue.is_empty() && !self.done {
+            match self.parts.next() {
+                Some(part) => self.push(part),
+                None => self.finish(),
+            }
+        }
+
+        self.queue.pop_front()
+    }
+}
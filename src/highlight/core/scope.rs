@@ -0,0 +1,110 @@
+// Tracking of the nesting of delimiters, and of the items which declare names
+// for their whole body, such as generic parameters and local variables.
+//
+// Like the classifications using it, this only looks at tokens: an item is
+// open from its keyword to the end of its body, or to the `;` ending an item
+// without one.
+
+/// The brace depth, and the parenthesis and bracket depth, of a token.
+#[deriving(Clone, Eq)]
+pub struct Nesting {
+    pub braces: uint,
+    pub parens: uint,
+}
+
+impl Nesting {
+    pub fn new() -> Nesting {
+        Nesting {
+            braces: 0,
+            parens: 0,
+        }
+    }
+
+    /// Records the next significant token.
+    pub fn push(&mut self, tok: &str) {
+        match tok {
+            "{" => self.braces += 1,
+            "}" if self.braces > 0 => self.braces -= 1,
+            "(" | "[" => self.parens += 1,
+            ")" | "]" if self.parens > 0 => self.parens -= 1,
+            _ => {}
+        }
+    }
+}
+
+#[deriving(Clone, Eq)]
+struct Scope<T> {
+    // brace depth of the item
+    depth: uint,
+    // whether the body of the item has been entered
+    opened: bool,
+    data: T,
+}
+
+/// The items open around a token, innermost last, with what each one
+/// declares.
+#[deriving(Clone, Eq)]
+pub struct Scopes<T> {
+    scopes: Vec<Scope<T>>,
+}
+
+impl<T> Scopes<T> {
+    pub fn new() -> Scopes<T> {
+        Scopes {
+            scopes: Vec::new(),
+        }
+    }
+
+    /// Opens an item at `nesting`, the nesting of its keyword.
+    pub fn open(&mut self, nesting: &Nesting, data: T) {
+        self.scopes.push(Scope {
+            depth: nesting.braces,
+            opened: false,
+            data: data,
+        });
+    }
+
+    /// Drops the innermost item, which turned out not to be one.
+    pub fn pop(&mut self) {
+        self.scopes.pop();
+    }
+
+    pub fn last_mut<'a>(&'a mut self) -> Option<&'a mut T> {
+        self.scopes.mut_last().map(|scope| &mut scope.data)
+    }
+
+    /// Returns whether `f` holds for what an open item declares.
+    pub fn any(&self, f: |&T| -> bool) -> bool {
+        for scope in self.scopes.iter() {
+            if f(&scope.data) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Records the next significant token, whose nesting is `nesting`. Every
+    /// token has to be pushed, in order, for the items to be right.
+    pub fn push(&mut self, tok: &str, nesting: &Nesting) {
+        let depth = nesting.braces;
+        match tok {
+            "{" => {
+                for scope in self.scopes.mut_iter() {
+                    if scope.depth == depth {
+                        scope.opened = true;
+                    }
+                }
+            }
+            "}" => {
+                let depth = if depth > 0 { depth - 1 } else { 0 };
+                self.scopes.retain(|scope| !(scope.opened && scope.depth == depth));
+            }
+            // An item without a body, such as `fn f<T>(x: T);`.
+            ";" if nesting.parens == 0 => {
+                self.scopes.retain(|scope| scope.opened || scope.depth != depth);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,197 @@
+// Classification of identifiers by their syntactic position.
+//
+// This is not a parser: it only looks at the tokens around an identifier and
+// at the generic parameters declared by the enclosing items, which is enough
+// to tell types, functions, fields and the like apart in most code.
+
+use std::mem;
+
+use super::scope::{Nesting, Scopes};
+
+// Items which introduce a name that may be followed by generic parameters.
+static GENERIC_ITEMS: &'static [&'static str] = &[
+    "fn", "struct", "enum", "union", "trait", "type",
+];
+
+// The generic parameters declared by an item.
+#[deriving(Clone, Eq)]
+struct Generics {
+    names: Vec<~str>,
+    // the const parameters among them
+    consts: Vec<~str>,
+}
+
+#[deriving(Clone, Eq)]
+pub struct Semantic {
+    prev: ~str,
+    prev2: ~str,
+    // depth of angle brackets while declaring generic parameters
+    angle: uint,
+    // set in trait bounds, after `T:`, `impl` or `dyn`, with the delimiter
+    // depth inside them
+    bounds: bool,
+    bounds_nesting: uint,
+    // set in a `where` clause
+    in_where: bool,
+    scopes: Scopes<Generics>,
+}
+
+fn is_upper(name: &str) -> bool {
+    name.chars().next().map_or(false, |c| c.is_uppercase())
+}
+
+fn is_constant_name(name: &str) -> bool {
+    name.len() > 1 && is_upper(name) && !name.chars().any(|c| c.is_lowercase())
+}
+
+fn is_ident(tok: &str) -> bool {
+    tok.chars().next().map_or(false, |c| c == '_' || c.is_alphabetic())
+}
+
+impl Semantic {
+    pub fn new() -> Semantic {
+        Semantic {
+            prev: "".to_owned(),
+            prev2: "".to_owned(),
+            angle: 0,
+            bounds: false,
+            bounds_nesting: 0,
+            in_where: false,
+            scopes: Scopes::new(),
+        }
+    }
+
+    fn is_generic(&self, name: &str) -> bool {
+        self.scopes.any(|generics| {
+            generics.names.iter().any(|n| n.as_slice() == name)
+        })
+    }
+
+    fn is_const(&self, name: &str) -> bool {
+        self.scopes.any(|generics| {
+            generics.consts.iter().any(|n| n.as_slice() == name)
+        })
+    }
+
+    /// Classifies the identifier `name`, which is followed by the tokens
+    /// `next` and `next2`. Returns `None` when its position tells nothing.
+    pub fn classify(&self, name: &str, next: &str, next2: &str) -> Option<&'static str> {
+        let prev = self.prev.as_slice();
+        let prev2 = self.prev2.as_slice();
+
+        let ty = match prev {
+            _ if self.is_generic(name) => "generic",
+
+            "fn" => "function",
+            "struct" | "enum" | "union" | "type" => "type",
+            "trait" | "dyn" => "trait",
+            "mod" | "crate" => "module",
+            // but not `*const T`
+            "const" | "static" if prev2 != "*" => "constant",
+            "mut" if prev2 == "static" => "constant",
+
+            // `x.method()`, `x.method::<T>()` or `x.field`
+            "." => match next {
+                "(" | "::" => "method",
+                _ => "field",
+            },
+
+            // `impl Trait for Type`, and the bounds of `T: A + B`, as opposed
+            // to `a + B` and the type of `const N: usize`
+            _ if next == "for" && prev == "impl" => "trait",
+            "+" if self.bounds && is_upper(name) => "trait",
+            ":" if self.is_generic(prev2) && !self.is_const(prev2) => "trait",
+
+            // `Type::Variant` as opposed to `module::Type`
+            "::" if is_constant_name(name) => "constant",
+            "::" if is_upper(name) && is_upper(prev2) => "variant",
+
+            _ => match next {
+                "::" if next2 == "<" || is_upper(name) => "type",
+                "::" => "module",
+                "(" if is_upper(name) => "type",
+                "(" => "function",
+                "!" => return None,
+                _ if is_constant_name(name) => "constant",
+                _ if is_upper(name) => "type",
+                _ => return None,
+            },
+        };
+
+        Some(ty)
+    }
+
+    /// Records the next significant token, whose nesting is `nesting`.
+    pub fn push(&mut self, tok: &str, nesting: &Nesting) {
+        self.scopes.push(tok, nesting);
+
+        if self.bounds {
+            match tok {
+                "<" | "(" | "[" => self.bounds_nesting += 1,
+                ">" | ")" | "]" if self.bounds_nesting > 0 => self.bounds_nesting -= 1,
+                ">>" if self.bounds_nesting > 0 => {
+                    self.bounds_nesting -= if self.bounds_nesting > 1 { 2 } else { 1 };
+                }
+                "," | "{" | ";" | "=" | ">" | ">>" | ")" | "]" => self.bounds = false,
+                _ => {}
+            }
+        }
+        match tok {
+            ":" if self.angle > 0 || self.in_where => {
+                self.bounds = true;
+                self.bounds_nesting = 0;
+            }
+            "impl" | "dyn" => {
+                self.bounds = true;
+                self.bounds_nesting = 0;
+            }
+            "where" => self.in_where = true,
+            "{" | ";" => self.in_where = false,
+            _ => {}
+        }
+
+        match tok {
+            "<" if self.angle > 0 => {
+                self.angle += 1;
+            }
+            "<" => {
+                let declares = self.prev.as_slice() == "impl" ||
+                    (is_ident(self.prev.as_slice()) &&
+                     GENERIC_ITEMS.iter().any(|item| *item == self.prev2.as_slice()));
+                if declares {
+                    self.angle = 1;
+                    self.scopes.open(nesting, Generics {
+                        names: Vec::new(),
+                        consts: Vec::new(),
+                    });
+                }
+            }
+            ">" if self.angle > 0 => {
+                self.angle -= 1;
+            }
+            ">>" if self.angle > 0 => {
+                self.angle -= if self.angle > 1 { 2 } else { 1 };
+            }
+            _ if self.angle == 1 && is_ident(tok) => {
+                match self.prev.as_slice() {
+                    "<" | "," | "const" if tok != "const" => {
+                        let is_const = self.prev.as_slice() == "const";
+                        match self.scopes.last_mut() {
+                            Some(generics) => {
+                                generics.names.push(tok.to_owned());
+                                if is_const {
+                                    generics.consts.push(tok.to_owned());
+                                }
+                            }
+                            None => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+
+        self.prev2 = mem::replace(&mut self.prev, tok.to_owned());
+    }
+}
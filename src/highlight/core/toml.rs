@@ -0,0 +1,129 @@
+// A small TOML highlighter, for the frontmatter of cargo scripts.
+//
+// Table headers get the `toml-table` class and keys `toml-key`; values and
+// comments get the classes of their Rust counterparts.
+
+use super::literal::{Segment, Piece};
+
+fn is_bare(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+fn is_value(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '+' || c == '.' || c == ':'
+}
+
+// Returns the end of the string starting at `lo`. Multi-line strings use
+// three quotes, and only basic strings (`"`) have escapes.
+fn string_end(text: &str, lo: uint) -> uint {
+    let quote = text.char_at(lo);
+    let triple = if quote == '"' { "\"\"\"" } else { "'''" };
+
+    if text.slice_from(lo).starts_with(triple) {
+        return match text.slice_from(lo + 3).find_str(triple) {
+            Some(i) => lo + 3 + i + 3,
+            None => text.len(),
+        };
+    }
+
+    let mut i = lo + 1;
+    while i < text.len() {
+        match text.char_at(i) {
+            '\\' if quote == '"' => i += 1,
+            '\n' => return i,
+            c if c == quote => return i + 1,
+            _ => {}
+        }
+        i = text.char_range_at(i).next;
+    }
+
+    text.len()
+}
+
+fn scan(text: &str, lo: uint, f: |char| -> bool) -> uint {
+    match text.slice_from(lo).find(|c: char| !f(c)) {
+        Some(i) => lo + i,
+        None => text.len(),
+    }
+}
+
+/// Splits TOML text into its tables, keys, values and comments.
+pub fn highlight(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut plain = 0;
+
+    // set where a key is expected: at the start of a line, and in inline
+    // tables
+    let mut at_key = true;
+    let mut at_line_start = true;
+    // depth of inline tables and of arrays, which may span lines
+    let mut braces = 0;
+    let mut brackets = 0;
+
+    let mut i = 0;
+    while i < text.len() {
+        let c = text.char_at(i);
+        let (ty, end) = match c {
+            '\n' => {
+                at_key = braces == 0 && brackets == 0;
+                at_line_start = true;
+                i += 1;
+                continue;
+            }
+            c if c.is_whitespace() => {
+                i = text.char_range_at(i).next;
+                continue;
+            }
+            '#' => ("comment", scan(text, i, |c| c != '\n')),
+            '[' if at_line_start && brackets == 0 => {
+                let end = scan(text, i, |c| c != '\n' && c != '#');
+                ("toml-table", i + text.slice(i, end).trim_right().len())
+            }
+            '"' | '\'' if at_key => ("toml-key", string_end(text, i)),
+            '"' | '\'' => ("string", string_end(text, i)),
+            c if at_key && is_bare(c) => ("toml-key", scan(text, i, is_bare)),
+            c if c.is_digit() || c == '+' || c == '-' => ("number", scan(text, i, is_value)),
+            c if c.is_alphabetic() => {
+                let end = scan(text, i, is_value);
+                let ty = match text.slice(i, end) {
+                    "true" | "false" => "boolval",
+                    "inf" | "nan" => "number",
+                    _ => "",
+                };
+                (ty, end)
+            }
+            _ => {
+                match c {
+                    '=' => at_key = false,
+                    '{' => {
+                        braces += 1;
+                        at_key = true;
+                    }
+                    '}' if braces > 0 => braces -= 1,
+                    '[' => brackets += 1,
+                    ']' if brackets > 0 => brackets -= 1,
+                    ',' if braces > 0 => at_key = true,
+                    _ => {}
+                }
+                at_line_start = false;
+                i = text.char_range_at(i).next;
+                continue;
+            }
+        };
+        at_line_start = false;
+
+        if ty != "" {
+            if i > plain {
+                segments.push(Piece("", plain, i));
+            }
+            segments.push(Piece(ty, i, end));
+            plain = end;
+        }
+        i = end;
+    }
+    if text.len() > plain {
+        segments.push(Piece("", plain, text.len()));
+    }
+
+    segments
+}
@@ -0,0 +1,147 @@
+// A tree view of the highlighted parts.
+//
+// Regions become nodes whose children are the regions and texts inside them,
+// and texts become leaves.
+
+use super::{Part, Start, End, Text, Span};
+
+/// A region, or a text when `text` is set.
+#[deriving(Clone, Encodable, Show)]
+pub struct Node {
+    pub ty: ~str,
+    pub span: Span,
+    pub text: Option<~str>,
+    pub children: Vec<Node>,
+}
+
+/// Builds a tree from parts, always with balanced nesting: an end closes the
+/// innermost node of its class, along with the nodes opened inside it, and
+/// `finish` closes the nodes which are still open.
+pub struct TreeBuilder {
+    // open nodes, innermost last
+    open: Vec<Node>,
+    nodes: Vec<Node>,
+}
+
+impl TreeBuilder {
+    pub fn new() -> TreeBuilder {
+        TreeBuilder {
+            open: Vec::new(),
+            nodes: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, node: Node) {
+        match self.open.mut_last() {
+            Some(parent) => parent.children.push(node),
+            None => self.nodes.push(node),
+        }
+    }
+
+    // Closes the innermost open node, at the end of `span`.
+    fn close(&mut self, span: &Span) {
+        let mut node = self.open.pop().unwrap();
+        node.span.hi = span.hi;
+        node.span.end = span.end.clone();
+        self.add(node);
+    }
+
+    pub fn start(&mut self, ty: &str, span: &Span) {
+        self.open.push(Node {
+            ty: ty.to_owned(),
+            span: span.clone(),
+            text: None,
+            children: Vec::new(),
+        });
+    }
+
+    /// Closes the innermost open node of class `ty`, if any.
+    pub fn end(&mut self, ty: &str, span: &Span) {
+        match self.open.iter().rposition(|node| node.ty.as_slice() == ty) {
+            Some(i) => {
+                while self.open.len() > i {
+                    self.close(span);
+                }
+            }
+            None => {}
+        }
+    }
+
+    pub fn text(&mut self, ty: &str, text: &str, span: &Span) {
+        self.add(Node {
+            ty: ty.to_owned(),
+            span: span.clone(),
+            text: Some(text.to_owned()),
+            children: Vec::new(),
+        });
+    }
+
+    pub fn push(&mut self, part: &Part) {
+        match *part {
+            Start(ref ty, ref span) => self.start(ty.as_slice(), span),
+            End(ref ty, ref span) => self.end(ty.as_slice(), span),
+            Text(ref ty, ref text, ref span) => self.text(ty.as_slice(), text.as_slice(), span),
+        }
+    }
+
+    /// Closes the open nodes, each at the end of its last child, and returns
+    /// the top-level nodes.
+    pub fn finish(mut self) -> Vec<Node> {
+        while !self.open.is_empty() {
+            let span = {
+                let node = self.open.last().unwrap();
+                node.children.last().map_or(node.span.clone(), |child| child.span.clone())
+            };
+            self.close(&span);
+        }
+
+        self.nodes
+    }
+}
+
+pub fn from_parts<T: Iterator<Part>>(mut parts: T) -> Vec<Node> {
+    let mut builder = TreeBuilder::new();
+    for part in parts {
+        builder.push(&part);
+    }
+
+    builder.finish()
+}
+
+fn push_parts(parts: &mut Vec<Part>, node: &Node) {
+    match node.text {
+        Some(ref text) => {
+            parts.push(Text(node.ty.clone(), text.clone(), node.span.clone()));
+        }
+        None => {
+            let start = Span {
+                lo: node.span.lo,
+                hi: node.span.lo,
+                start: node.span.start.clone(),
+                end: node.span.start.clone(),
+            };
+            let end = Span {
+                lo: node.span.hi,
+                hi: node.span.hi,
+                start: node.span.end.clone(),
+                end: node.span.end.clone(),
+            };
+
+            parts.push(Start(node.ty.clone(), start));
+            for child in node.children.iter() {
+                push_parts(parts, child);
+            }
+            parts.push(End(node.ty.clone(), end));
+        }
+    }
+}
+
+/// Flattens nodes back into parts, with a start and an end for each region.
+pub fn to_parts(nodes: &[Node]) -> Vec<Part> {
+    let mut parts = Vec::new();
+    for node in nodes.iter() {
+        push_parts(&mut parts, node);
+    }
+
+    parts
+}
@@ -0,0 +1,195 @@
+// Tracking of the local bindings of functions, so that each variable keeps the
+// same colour at every use.
+//
+// Like the semantic classification, this only looks at tokens: bindings are
+// the names declared by function and closure parameters, and by `let` and
+// `for` patterns. Match arms don't declare anything here.
+
+use super::scope::{Nesting, Scopes};
+
+#[deriving(Clone, Eq)]
+enum Pattern {
+    NoPattern,
+    // after `fn`, until its parameters are opened
+    FnName,
+    FnParams,
+    // `let` pattern, until `=`, `:` or `;`
+    Let,
+    // `for` pattern, until `in`
+    For,
+    // closure parameters, until the closing `|`
+    ClosureParams,
+}
+
+#[deriving(Clone, Eq)]
+pub struct Variables {
+    prev: ~str,
+    pattern: Pattern,
+    // delimiter depth inside the pattern
+    nesting: uint,
+    // set in the type of a parameter, with its angle bracket depth
+    in_type: bool,
+    angle: uint,
+    // the variables of each function, in order of declaration
+    functions: Scopes<Vec<~str>>,
+}
+
+// Tokens after which a `|` opens the parameters of a closure rather than being
+// an operator or a pattern alternative.
+static CLOSURE_PREV: &'static [&'static str] = &[
+    "(", "[", "{", ",", ";", "=", "=>", ":", "&&", "||", "move", "return",
+];
+
+// Tokens after which `for` starts a loop, as opposed to `impl Trait for`.
+static LOOP_PREV: &'static [&'static str] = &[
+    "", "{", "}", ";", ":", "=>",
+];
+
+impl Variables {
+    pub fn new() -> Variables {
+        Variables {
+            prev: "".to_owned(),
+            pattern: NoPattern,
+            nesting: 0,
+            in_type: false,
+            angle: 0,
+            functions: Scopes::new(),
+        }
+    }
+
+    // Delimiter depth of the pattern itself: parameters are inside
+    // parentheses.
+    fn base(&self) -> uint {
+        match self.pattern {
+            FnParams => 1,
+            _ => 0,
+        }
+    }
+
+    fn is_binding(&self, name: &str, next: &str) -> bool {
+        let in_pattern = match self.pattern {
+            NoPattern | FnName => false,
+            _ => true,
+        };
+        let is_lower = name.chars().next().map_or(false, |c| c == '_' || c.is_lowercase());
+
+        // `Some(x)` and `Point { x: a }` bind `x` and `a`, not `Some` or the
+        // field name.
+        in_pattern && !self.in_type && is_lower && name != "_" &&
+            self.prev.as_slice() != "::" &&
+            !(next == ":" && self.nesting > self.base()) &&
+            match next {
+                "(" | "::" | "{" | "!" => false,
+                _ => true,
+            }
+    }
+
+    /// Returns the index of the local variable `name`, followed by `next`, in
+    /// the enclosing function. Names in a binding position are declared.
+    pub fn classify(&mut self, name: &str, next: &str) -> Option<uint> {
+        let is_binding = self.is_binding(name, next);
+        let is_use = {
+            // `x` in `Point { x: 1 }` is a field.
+            let prev = self.prev.as_slice();
+            let is_field = next == ":" && (prev == "{" || prev == ",");
+            prev != "." && next != "::" && next != "!" && !is_field
+        };
+        let names = match self.functions.last_mut() {
+            Some(names) => names,
+            None => return None,
+        };
+
+        match names.iter().position(|n| n.as_slice() == name) {
+            Some(index) if is_binding || is_use => Some(index),
+            None if is_binding => {
+                names.push(name.to_owned());
+                Some(names.len() - 1)
+            }
+            _ => None,
+        }
+    }
+
+    fn end_pattern(&mut self) {
+        self.pattern = NoPattern;
+        self.in_type = false;
+        self.angle = 0;
+    }
+
+    // Follows the delimiters and the parameter types inside a pattern.
+    fn push_pattern(&mut self, tok: &str) {
+        match tok {
+            "(" | "[" | "{" => self.nesting += 1,
+            ")" | "]" | "}" if self.nesting > 0 => self.nesting -= 1,
+            "<" if self.in_type => self.angle += 1,
+            ">" if self.in_type && self.angle > 0 => self.angle -= 1,
+            ">>" if self.in_type => {
+                self.angle -= if self.angle > 1 { 2 } else { self.angle };
+            }
+            _ => {}
+        }
+
+        if self.nesting != self.base() || self.angle > 0 {
+            if self.pattern == FnParams && self.nesting == 0 {
+                self.end_pattern();
+            }
+            return;
+        }
+
+        match (self.pattern, tok) {
+            (FnParams, ":") | (ClosureParams, ":") => self.in_type = true,
+            (FnParams, ",") | (ClosureParams, ",") => self.in_type = false,
+            (ClosureParams, "|") => self.end_pattern(),
+            (Let, "=") | (Let, ":") | (Let, ";") => self.end_pattern(),
+            (For, "in") => self.end_pattern(),
+            _ => {}
+        }
+    }
+
+    /// Records the next significant token, whose nesting is `nesting`, and
+    /// which is followed by `next`.
+    pub fn push(&mut self, tok: &str, next: &str, nesting: &Nesting) {
+        self.functions.push(tok, nesting);
+
+        match self.pattern {
+            NoPattern => {
+                let prev = self.prev.as_slice();
+                match tok {
+                    "fn" => {
+                        self.functions.open(nesting, Vec::new());
+                        self.pattern = FnName;
+                        self.angle = 0;
+                    }
+                    "let" => self.pattern = Let,
+                    // `for<'a>` is a higher-ranked bound, as in `F: for<'a> Fn(&'a u8)`.
+                    "for" if next != "<" && LOOP_PREV.iter().any(|p| *p == prev) => {
+                        self.pattern = For;
+                    }
+                    "|" if CLOSURE_PREV.iter().any(|p| *p == prev) => self.pattern = ClosureParams,
+                    _ => {}
+                }
+                self.nesting = 0;
+            }
+            // Parameters start at the first parenthesis outside of the
+            // generic parameters, which may contain `Fn(A) -> B` bounds.
+            FnName => match tok {
+                // `fn(A) -> B` is a type, not a function.
+                "(" if self.prev.as_slice() == "fn" => {
+                    self.functions.pop();
+                    self.end_pattern();
+                }
+                "<" => self.angle += 1,
+                ">" if self.angle > 0 => self.angle -= 1,
+                ">>" => self.angle -= if self.angle > 1 { 2 } else { self.angle },
+                "(" if self.angle == 0 => {
+                    self.pattern = FnParams;
+                    self.nesting = 1;
+                }
+                ";" | "{" => self.end_pattern(),
+                _ => {}
+            },
+            _ => self.push_pattern(tok),
+        }
+
+        self.prev = tok.to_owned();
+    }
+}
@@ -2,7 +2,6 @@
 #![crate_type = "dylib"]
 #![license = "MIT"]
 
-extern crate syntax;
 extern crate serialize;
 extern crate collections;
 